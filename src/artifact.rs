@@ -0,0 +1,154 @@
+use std::fs;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+
+use crate::encoding;
+use crate::utils;
+
+const IMAGE_EXTENSIONS: [&str; 6] = ["svg", "png", "jpg", "jpeg", "gif", "webp"];
+
+/// How a file produced by a command is inserted in the chapter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Embed {
+    /// Copy the file next to the chapters and reference it.
+    #[default]
+    Copy,
+    /// Reference the file as a base64 data URI.
+    DataUri,
+    /// Insert the content of an SVG file.
+    Inline,
+}
+
+impl Embed {
+    pub fn parse(embed: &str) -> Result<Embed> {
+        match embed {
+            "copy" => Ok(Embed::Copy),
+            "data-uri" => Ok(Embed::DataUri),
+            "inline" => Ok(Embed::Inline),
+            _ => bail!("Unknown embed mode '{embed}', expected copy, data-uri or inline"),
+        }
+    }
+}
+
+/// Directory in which the artifacts of a chapter are copied.
+#[derive(Clone, Debug)]
+pub struct ArtifactDir {
+    pub path: PathBuf,
+    /// The same directory, relative to the chapter.
+    pub url: String,
+}
+
+impl ArtifactDir {
+    /// Artifacts of the chapter `chapter_path` are copied to `<src_dir>/<dir>/<chapter_path without extension>/`.
+    pub fn new(src_dir: &Path, dir: &str, chapter_path: &Path) -> ArtifactDir {
+        let chapter_dir = chapter_path.with_extension("");
+        let depth = chapter_path
+            .parent()
+            .map_or(0, |parent| parent.components().count());
+
+        let mut url = "../".repeat(depth);
+        url.push_str(dir.trim_end_matches('/'));
+        for component in chapter_dir.components() {
+            url.push('/');
+            url.push_str(&component.as_os_str().to_string_lossy());
+        }
+
+        ArtifactDir {
+            path: src_dir.join(dir).join(chapter_dir),
+            url,
+        }
+    }
+}
+
+/// Insert the file `path`, relative to `working_dir`, produced by the directive at position `index` of a chapter.
+///
+/// Images are inserted as images, other files as links.
+/// Without a directory to copy them to, files are referenced where they are.
+pub fn embed(
+    path: &str,
+    working_dir: &Path,
+    embed: Embed,
+    alt: Option<&str>,
+    dir: Option<&ArtifactDir>,
+    index: Option<usize>,
+) -> Result<String> {
+    let source = working_dir.join(path);
+    let bytes = fs::read(&source)
+        .with_context(|| format!("Unable to read artifact {}", source.display()))?;
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string());
+    let extension = Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let alt = alt.unwrap_or(&file_name);
+
+    let url = match embed {
+        Embed::Inline => {
+            if extension != "svg" {
+                bail!("Only SVG artifacts can be inlined, not {path}");
+            }
+            let svg = String::from_utf8(bytes)
+                .with_context(|| format!("Artifact {path} is not valid UTF-8"))?;
+            return Ok(inline_svg(&svg));
+        }
+        Embed::DataUri => encoding::data_uri(&bytes),
+        Embed::Copy => match dir {
+            None => path.replace('\\', "/"),
+            Some(dir) => {
+                let destination = destination(path, &file_name, index);
+                utils::fs::write_if_changed(&dir.path.join(&destination), &bytes)?;
+                format!("{}/{}", dir.url, destination)
+            }
+        },
+    };
+    let url = match url.contains(' ') {
+        true => format!("<{url}>"),
+        false => url,
+    };
+
+    match IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+        true => Ok(format!("![{alt}]({url})")),
+        false => Ok(format!("[{alt}]({url})")),
+    }
+}
+
+// Path of a copied artifact in the directory of its chapter, so that files with the same name don't overwrite each other.
+// Paths inside the working directory are kept, the other files are prefixed by the position of their directive.
+fn destination(path: &str, file_name: &str, index: Option<usize>) -> String {
+    let components = Path::new(path).components();
+    if components
+        .clone()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        let names = components
+            .filter_map(|c| match c {
+                Component::Normal(name) => Some(name.to_string_lossy()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        return names.join("/");
+    }
+    match index {
+        Some(index) => format!("{index}-{file_name}"),
+        None => file_name.to_string(),
+    }
+}
+
+// SVG inserted in HTML, without its XML prolog.
+// Blank lines are removed, as they would end the HTML block in markdown.
+fn inline_svg(svg: &str) -> String {
+    let start = svg.find("<svg").unwrap_or(0);
+    svg[start..]
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
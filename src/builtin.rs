@@ -0,0 +1,190 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use similar::DiffTag;
+use similar::TextDiff;
+
+use crate::utils::text::escape_html;
+
+const DIFF_STYLE: &str = "font-family:monospace;white-space:pre";
+const DELETED_STYLE: &str = "background-color:#ffdddd";
+const INSERTED_STYLE: &str = "background-color:#ddffdd";
+
+/// Output of a builtin directive.
+pub enum Output {
+    /// Text, post-processed and escaped like the output of a command.
+    Text(String),
+    /// HTML inserted as it is.
+    Html(String),
+}
+
+/// Content of a file, selected like mdbook's `{{#include}}`:
+/// - `path`: the whole file
+/// - `path:N`: only the line `N`
+/// - `path:N:M`, `path:N:` or `path::M`: the lines from `N` to `M`, both included
+/// - `path:anchor`: the lines between `ANCHOR: anchor` and `ANCHOR_END: anchor`
+///
+/// Lines containing anchor markers are never included.
+pub fn include(spec: &str, working_dir: &Path) -> Result<String> {
+    let (path, start, end) = split_include(spec);
+
+    let content = fs::read_to_string(working_dir.join(path))
+        .with_context(|| format!("Unable to read file {path}"))?;
+    let lines: Vec<&str> = content.lines().collect();
+
+    let selected: Vec<&str> = match (start, end) {
+        (None, _) => lines,
+        (Some(anchor), None) if anchor.parse::<usize>().is_err() => {
+            let begin = lines
+                .iter()
+                .position(|l| is_anchor(l, "ANCHOR:", anchor))
+                .ok_or_else(|| anyhow!("Anchor '{anchor}' not found in {path}"))?;
+            let len = lines[begin + 1..]
+                .iter()
+                .position(|l| is_anchor(l, "ANCHOR_END:", anchor))
+                .ok_or_else(|| anyhow!("Anchor '{anchor}' is never closed in {path}"))?;
+            lines[begin + 1..begin + 1 + len].to_vec()
+        }
+        (Some(start), end) => {
+            let line_number = |n: &str| {
+                n.parse::<usize>()
+                    .map_err(|_| anyhow!("Invalid line number '{n}' in '{spec}'"))
+            };
+            let first = match start {
+                "" => 1,
+                n => line_number(n)?,
+            };
+            let last = match end {
+                None => first,
+                Some("") => lines.len(),
+                Some(n) => line_number(n)?,
+            };
+            if first == 0 || first > last {
+                bail!("Invalid line range in '{spec}'");
+            }
+            lines
+                .into_iter()
+                .skip(first - 1)
+                .take(last - first + 1)
+                .collect()
+        }
+    };
+
+    Ok(selected
+        .into_iter()
+        .filter(|l| !l.contains("ANCHOR:") && !l.contains("ANCHOR_END:"))
+        .map(|l| format!("{l}\n"))
+        .collect())
+}
+
+// Split the line range or the anchor ending an include spec from its path.
+// The spec is parsed from the right, so that paths may contain colons, like the drive of Windows paths.
+fn split_include(spec: &str) -> (&str, Option<&str>, Option<&str>) {
+    let is_number = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    let is_name = |s: &str| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_-".contains(c))
+    };
+
+    let Some((rest, last)) = spec.rsplit_once(':') else {
+        return (spec, None, None);
+    };
+    if let Some((path, first)) = rest.rsplit_once(':') {
+        if is_number(first) && is_number(last) {
+            return (path, Some(first), Some(last));
+        }
+    }
+    if is_name(last) {
+        return (rest, Some(last), None);
+    }
+    (spec, None, None)
+}
+
+fn is_anchor(line: &str, marker: &str, anchor: &str) -> bool {
+    line.split_once(marker)
+        .and_then(|(_, name)| name.split_whitespace().next())
+        == Some(anchor)
+}
+
+/// Unified diff between two files, with `context` lines around each change.
+pub fn diff(old: &str, new: &str, working_dir: &Path, context: usize) -> Result<String> {
+    let (old_content, new_content) = read_pair(old, new, working_dir)?;
+
+    Ok(TextDiff::from_lines(&old_content, &new_content)
+        .unified_diff()
+        .context_radius(context)
+        .header(old, new)
+        .to_string())
+}
+
+/// Side by side diff between two files, as an HTML table.
+pub fn diff_side_by_side(
+    old: &str,
+    new: &str,
+    working_dir: &Path,
+    context: usize,
+) -> Result<String> {
+    let (old_content, new_content) = read_pair(old, new, working_dir)?;
+    let diff = TextDiff::from_lines(&old_content, &new_content);
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+
+    let mut table = format!(
+        "<table style=\"{DIFF_STYLE}\">\n<thead><tr><th colspan=\"2\">{}</th><th colspan=\"2\">{}</th></tr></thead>\n<tbody>\n",
+        escape_html(old),
+        escape_html(new)
+    );
+
+    for (i, group) in diff.grouped_ops(context).iter().enumerate() {
+        if i > 0 {
+            table.push_str("<tr><td colspan=\"4\">⋯</td></tr>\n");
+        }
+        for op in group {
+            let (tag, old_range, new_range) = op.as_tag_tuple();
+            let style = match tag {
+                DiffTag::Equal => (None, None),
+                DiffTag::Delete => (Some(DELETED_STYLE), None),
+                DiffTag::Insert => (None, Some(INSERTED_STYLE)),
+                DiffTag::Replace => (Some(DELETED_STYLE), Some(INSERTED_STYLE)),
+            };
+
+            for row in 0..old_range.len().max(new_range.len()) {
+                let cell = |lines: &[&str], index: usize, style: Option<&str>| {
+                    (
+                        (index + 1).to_string(),
+                        escape_html(lines[index].trim_end_matches(['\n', '\r'])),
+                        style.map(|s| format!(" style=\"{s}\"")).unwrap_or_default(),
+                    )
+                };
+                let (old_number, old_line, old_style) = match row < old_range.len() {
+                    true => cell(old_lines, old_range.start + row, style.0),
+                    false => Default::default(),
+                };
+                let (new_number, new_line, new_style) = match row < new_range.len() {
+                    true => cell(new_lines, new_range.start + row, style.1),
+                    false => Default::default(),
+                };
+
+                table.push_str(&format!(
+                    "<tr><td>{old_number}</td><td{old_style}>{old_line}</td><td>{new_number}</td><td{new_style}>{new_line}</td></tr>\n"
+                ));
+            }
+        }
+    }
+
+    table.push_str("</tbody>\n</table>\n");
+    Ok(table)
+}
+
+fn read_pair(old: &str, new: &str, working_dir: &Path) -> Result<(String, String)> {
+    let read = |path: &str| {
+        fs::read_to_string(working_dir.join(path))
+            .with_context(|| format!("Unable to read file {path}"))
+    };
+    Ok((read(old)?, read(new)?))
+}
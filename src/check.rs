@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Result;
+
+use mdbook::book::BookItem;
+use mdbook::MDBook;
+
+use crate::cmdrun::RunOptions;
+use crate::config::Ansi;
+use crate::config::Config;
+use crate::snapshot::unified_diff;
+use crate::snapshot::SnapshotStore;
+use crate::CmdRun;
+
+/// What to do with the recorded snapshots of directive outputs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SnapshotMode {
+    /// Snapshots are not used.
+    #[default]
+    Ignore,
+    /// Every directive output must match its snapshot.
+    Verify,
+    /// Snapshots are rewritten from the outputs of the directives which succeed,
+    /// and those of directives which no longer exist are removed.
+    Update,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CheckOptions {
+    /// Directives without an exit code flag are expected to return 0.
+    pub strict: bool,
+    pub snapshots: SnapshotMode,
+}
+
+/// Result of running a single directive during a check.
+pub struct DirectiveReport {
+    /// Position of the directive in its chapter, starting at 1.
+    pub index: usize,
+    pub command: String,
+    /// Why the directive failed, `None` if it succeeded.
+    pub error: Option<String>,
+    /// Difference with the snapshot, when it doesn't match.
+    pub diff: Option<String>,
+    pub duration: Duration,
+}
+
+/// Results of all the directives of a chapter.
+pub struct ChapterReport {
+    pub name: String,
+    pub path: String,
+    pub directives: Vec<DirectiveReport>,
+    pub duration: Duration,
+}
+
+/// Results of checking a whole book.
+pub struct Report {
+    pub chapters: Vec<ChapterReport>,
+    pub duration: Duration,
+}
+
+impl ChapterReport {
+    fn failures(&self) -> impl Iterator<Item = &DirectiveReport> {
+        self.directives.iter().filter(|d| d.error.is_some())
+    }
+}
+
+impl Report {
+    pub fn directive_count(&self) -> usize {
+        self.chapters.iter().map(|c| c.directives.len()).sum()
+    }
+
+    pub fn failure_count(&self) -> usize {
+        self.chapters.iter().map(|c| c.failures().count()).sum()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failure_count() == 0
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chapter in &self.chapters {
+            writeln!(
+                f,
+                "{}: {} directives, {} failed ({:.2?})",
+                chapter.path,
+                chapter.directives.len(),
+                chapter.failures().count(),
+                chapter.duration
+            )?;
+        }
+
+        if !self.is_success() {
+            writeln!(f)?;
+            writeln!(f, "Failures:")?;
+            for chapter in &self.chapters {
+                for directive in chapter.failures() {
+                    writeln!(
+                        f,
+                        "- {} ({}), directive #{}: {}",
+                        chapter.name,
+                        chapter.path,
+                        directive.index,
+                        directive.error.as_deref().unwrap_or_default()
+                    )?;
+                    writeln!(f, "    cmdrun {}", directive.command.trim())?;
+                    if let Some(diff) = &directive.diff {
+                        write!(f, "{diff}")?;
+                    }
+                }
+            }
+        }
+
+        writeln!(f)?;
+        write!(
+            f,
+            "{} directives in {} chapters, {} failed ({:.2?})",
+            self.directive_count(),
+            self.chapters.len(),
+            self.failure_count(),
+            self.duration
+        )
+    }
+}
+
+/// Run every directive of the book located in `book_dir`, without rendering it.
+///
+/// Only chapters containing at least one directive are part of the report.
+pub fn check(book_dir: &Path, options: &CheckOptions) -> Result<Report> {
+    let book = MDBook::load(book_dir)?;
+    let src_dir = book.root.join(&book.config.book.src);
+    let store = SnapshotStore::new(&book.root);
+    let config = Config::from_book_config(&book.config)?;
+    // Snapshots keep ANSI escape codes, they are rendered when building the book
+    let book_options = RunOptions::for_book(&config, &book.root, &src_dir)?;
+    let book_options = RunOptions {
+        exit_code: options.strict.then_some(0).or(book_options.exit_code),
+        ansi: Ansi::Keep,
+        book_json: RunOptions::book_json(&book.book)?,
+        build_dir: Some(book.root.join(&book.config.build.build_dir)),
+        ..book_options
+    };
+    let renderers = renderers(&book.config);
+    let start = Instant::now();
+    let mut chapters = vec![];
+    // Snapshots of the directives run, the other ones are stale
+    let mut snapshots = HashSet::new();
+
+    for item in book.book.iter() {
+        let chapter = match item {
+            BookItem::Chapter(chapter) => chapter,
+            BookItem::PartTitle(_) | BookItem::Separator => continue,
+        };
+        let path = match &chapter.path {
+            Some(path) => path,
+            None => continue,
+        };
+        let directives = CmdRun::directives(&chapter.content);
+        if directives.is_empty() {
+            continue;
+        }
+
+        let run_options = book_options.for_chapter(&config, chapter);
+        let chapter_start = Instant::now();
+        let mut reports = vec![];
+
+        for (index, (command, inline)) in directives.into_iter().enumerate() {
+            let index = index + 1;
+            let directive_start = Instant::now();
+            let directive_options = run_options.for_directive(index);
+            let mut error = None;
+            let mut diff = None;
+
+            // The main output, then those of the renderers which get a different one
+            let mut variants = vec![(None, directive_options.clone())];
+            for renderer in &renderers {
+                let options = RunOptions {
+                    renderer: Some(renderer.clone()),
+                    ..directive_options.clone()
+                };
+                let variant = CmdRun::snapshot_variant(&command, &options);
+                if variant.is_some() && !variants.iter().any(|(v, _)| *v == variant) {
+                    variants.push((variant, options));
+                }
+            }
+
+            for (variant, variant_options) in variants {
+                let outcome = CmdRun::run_directive(command.clone(), inline, &variant_options)?;
+                let (variant_error, variant_diff) = match options.snapshots {
+                    SnapshotMode::Ignore => (outcome.error, None),
+                    SnapshotMode::Update => {
+                        snapshots.insert(store.path(path, index, variant));
+                        // A failing directive keeps its previous snapshot
+                        if outcome.error.is_none() {
+                            store.write(path, index, variant, &outcome.text)?;
+                        }
+                        (outcome.error, None)
+                    }
+                    SnapshotMode::Verify => {
+                        let snapshot_path = store.path(path, index, variant);
+                        match store.read(path, index, variant)? {
+                            None => (
+                                outcome.error.or_else(|| {
+                                    Some(format!("missing snapshot {}", snapshot_path.display()))
+                                }),
+                                None,
+                            ),
+                            Some(snapshot) if snapshot != outcome.text => (
+                                outcome.error.or_else(|| {
+                                    Some(format!(
+                                        "output differs from snapshot {}",
+                                        snapshot_path.display()
+                                    ))
+                                }),
+                                Some(unified_diff(&snapshot, &outcome.text)),
+                            ),
+                            Some(_) => (outcome.error, None),
+                        }
+                    }
+                };
+                if error.is_none() {
+                    error = variant_error;
+                    diff = variant_diff;
+                }
+            }
+
+            reports.push(DirectiveReport {
+                index,
+                command,
+                error,
+                diff,
+                duration: directive_start.elapsed(),
+            });
+        }
+
+        chapters.push(ChapterReport {
+            name: chapter.name.clone(),
+            path: path.display().to_string(),
+            directives: reports,
+            duration: chapter_start.elapsed(),
+        });
+    }
+
+    if options.snapshots == SnapshotMode::Update {
+        store.retain(&snapshots)?;
+    }
+
+    Ok(Report {
+        chapters,
+        duration: start.elapsed(),
+    })
+}
+
+// Renderers the book is built for, from its `[output]` tables.
+fn renderers(config: &mdbook::Config) -> Vec<String> {
+    match config.get("output").and_then(|output| output.as_table()) {
+        Some(outputs) if !outputs.is_empty() => outputs.keys().cloned().collect(),
+        _ => vec![String::from("html")],
+    }
+}
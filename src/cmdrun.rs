@@ -1,41 +1,323 @@
-use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fs;
+use std::io;
+use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
+use std::process::Output;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::thread;
 
+use anyhow::anyhow;
+use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
 use cfg_if::cfg_if;
 use lazy_static::lazy_static;
 use regex::Captures;
 use regex::Regex;
-use serde::Deserialize;
+use tempfile::TempDir;
 
 use mdbook::book::Book;
+use mdbook::book::BookItem;
 use mdbook::book::Chapter;
 use mdbook::preprocess::{Preprocessor, PreprocessorContext};
 
+use crate::artifact;
+use crate::artifact::ArtifactDir;
+use crate::builtin;
+use crate::config::Ansi;
+use crate::config::Config;
+use crate::config::Escape;
+use crate::config::WorkingDir;
+use crate::directive::Directive;
+use crate::directive::Format;
+use crate::encoding;
+use crate::encoding::Binary;
+use crate::exit_code;
+use crate::exit_code::ExitCodes;
+use crate::normalize::Normalize;
+use crate::redact::Redaction;
+use crate::snapshot::SnapshotStore;
+use crate::table;
+use crate::utils;
 use crate::utils::map_chapter;
+use crate::utils::words;
+use crate::vars;
 
 pub struct CmdRun;
 
 lazy_static! {
-    static ref CMDRUN_REG_NEWLINE: Regex = Regex::new(r"<!--[ ]*cmdrun (.*?)-->\r?\n")
-        .expect("Failed to init regex for finding newline pattern");
-    static ref CMDRUN_REG_INLINE: Regex = Regex::new(r"<!--[ ]*cmdrun (.*?)-->")
-        .expect("Failed to init regex for finding inline pattern");
+    // A directive followed by a newline is replaced along with the newline,
+    // otherwise it is considered inline.
+    static ref CMDRUN_REG: Regex = Regex::new(r"<!--[ ]*cmdrun (.*?)-->(\r?\n)?")
+        .expect("Failed to init regex for finding cmdrun pattern");
 }
 
+/// What running a single cmdrun directive produced.
+pub struct Outcome {
+    /// Text inserted in place of the directive.
+    pub text: String,
+    /// Why the directive failed, `None` if it succeeded.
+    pub error: Option<String>,
+}
+
+/// How the directives of a chapter are run and rendered.
+#[derive(Clone, Debug, Default)]
+pub struct RunOptions {
+    /// Directory in which the commands are run.
+    pub working_dir: String,
+    /// Exit code of the directives without an exit code flag, any code if `None`.
+    pub exit_code: Option<i32>,
+    /// What to do with ANSI escape codes in the output.
+    pub ansi: Ansi,
+    /// Renderer the book is built for, `None` when not building the book.
+    pub renderer: Option<String>,
+    /// Replacements applied to the output of commands, before anything else.
+    pub redaction: Redaction,
+    /// How the output is escaped, when directives don't use `--escape`.
+    pub escape: Escape,
+    /// Normalization of the output, so that it doesn't depend on the machine.
+    pub normalize: Normalize,
+    /// Where `--artifact` files are copied, they are referenced where they are if `None`.
+    pub artifact_dir: Option<ArtifactDir>,
+    /// Commands are run in a copy of the working directory, as with `--isolated`.
+    pub isolated: bool,
+    /// Commands are run directly rather than through a shell, as with `--exec`.
+    pub exec: bool,
+    /// Output which isn't valid text is an error, as with `--strict-encoding`.
+    pub strict_encoding: bool,
+    /// Root directory of the book, `None` when not building a book.
+    pub book_root: Option<PathBuf>,
+    /// Source directory of the book, `None` when not building a book.
+    pub src_dir: Option<PathBuf>,
+    /// Build directory of the book, which isolated commands don't copy.
+    pub build_dir: Option<PathBuf>,
+    /// Chapter whose directives are run.
+    pub chapter: Option<ChapterInfo>,
+    /// Position of the directive in its chapter, starting at 1.
+    pub directive_index: Option<usize>,
+    /// The whole book as JSON, for `--with-book-json`, only when a directive uses it.
+    pub book_json: Option<Arc<str>>,
+    /// Variables interpolated in directives, along with the built-in ones.
+    pub vars: BTreeMap<String, String>,
+    /// Commands run as `@name`, by their names.
+    pub aliases: BTreeMap<String, String>,
+}
+
+/// What commands know about the chapter they are run for.
+#[derive(Clone, Debug, Default)]
+pub struct ChapterInfo {
+    pub name: String,
+    /// Path of the chapter's source file, relative to the source directory.
+    pub path: Option<PathBuf>,
+    /// Section number, like `1.2`, `None` for unnumbered chapters.
+    pub section: Option<String>,
+    /// The chapter as JSON, for `--with-chapter-json`, only when a directive uses it.
+    pub json: Option<Arc<str>>,
+}
+
+impl RunOptions {
+    /// Options shared by every chapter of the book located in `book_root`.
+    pub fn for_book(config: &Config, book_root: &Path, src_dir: &Path) -> Result<RunOptions> {
+        Ok(RunOptions {
+            redaction: config.redaction()?,
+            escape: config.escape,
+            normalize: Normalize::new(&config.normalize, book_root),
+            exit_code: config.default_exit_code.code(),
+            isolated: config.isolated,
+            exec: config.exec,
+            strict_encoding: config.strict_encoding,
+            vars: config.vars(),
+            aliases: config.aliases.clone(),
+            book_root: Some(book_root.to_path_buf()),
+            src_dir: Some(src_dir.to_path_buf()),
+            ..Default::default()
+        })
+    }
+
+    /// Options to run the directives of a chapter, from the options of its book.
+    pub fn for_chapter(&self, config: &Config, chapter: &Chapter) -> RunOptions {
+        let (book_root, src_dir) = match (&self.book_root, &self.src_dir) {
+            (Some(book_root), Some(src_dir)) => (book_root, src_dir),
+            _ => return self.clone(),
+        };
+        let working_dir = match config.working_dir {
+            WorkingDir::Chapter => CmdRun::chapter_working_dir(src_dir, chapter),
+            WorkingDir::BookRoot => book_root.to_string_lossy().into_owned(),
+            WorkingDir::Src => src_dir.to_string_lossy().into_owned(),
+            WorkingDir::RepoRoot => Self::repo_root(book_root).to_string_lossy().into_owned(),
+        };
+
+        RunOptions {
+            working_dir,
+            chapter: Some(ChapterInfo {
+                name: chapter.name.clone(),
+                path: chapter.path.clone(),
+                section: chapter
+                    .number
+                    .as_ref()
+                    .map(|number| number.to_string().trim_end_matches('.').to_string()),
+                json: CmdRun::uses_flag(&chapter.content, "--with-chapter-json")
+                    .then(|| serde_json::to_string(chapter).ok().map(Arc::from))
+                    .flatten(),
+            }),
+            artifact_dir: chapter
+                .path
+                .as_ref()
+                .map(|path| ArtifactDir::new(src_dir, config.artifact_dir(), path)),
+            ..self.clone()
+        }
+    }
+
+    /// The book as JSON for `--with-book-json`, `None` if no directive uses it.
+    pub fn book_json(book: &Book) -> Result<Option<Arc<str>>> {
+        let used = book.iter().any(|item| match item {
+            BookItem::Chapter(chapter) => CmdRun::uses_flag(&chapter.content, "--with-book-json"),
+            BookItem::PartTitle(_) | BookItem::Separator => false,
+        });
+        match used {
+            true => Ok(Some(serde_json::to_string(book)?.into())),
+            false => Ok(None),
+        }
+    }
+
+    /// Options to run the directive at position `index` of a chapter, starting at 1.
+    pub fn for_directive(&self, index: usize) -> RunOptions {
+        RunOptions {
+            directive_index: Some(index),
+            ..self.clone()
+        }
+    }
+
+    /// Value of a variable: a configured one, or the built-in `book_root` (also named `root`), `src_dir` and `chapter_dir`.
+    fn var(&self, name: &str) -> Option<String> {
+        if let Some(value) = self.vars.get(name) {
+            return Some(value.clone());
+        }
+        match name {
+            "book_root" | "root" => self.book_root.as_deref().map(absolute),
+            "src_dir" => self.src_dir.as_deref().map(absolute),
+            "chapter_dir" => {
+                let path = self.chapter.as_ref()?.path.as_ref()?;
+                let dir = self.src_dir.as_ref()?.join(path.parent()?);
+                Some(absolute(&dir))
+            }
+            _ => None,
+        }
+    }
+
+    /// Expand the alias a command starts with, as `@name args`, into the aliased command followed by the arguments.
+    /// Variables are interpolated in the aliased command.
+    fn expand_alias(&self, command: &str) -> Result<String> {
+        let Some(alias) = command.strip_prefix('@') else {
+            return Ok(command.to_string());
+        };
+        let (name, args) = alias.split_once(char::is_whitespace).unwrap_or((alias, ""));
+        let aliased = match self.aliases.get(name) {
+            Some(aliased) => vars::interpolate(aliased, |name| self.var(name))?,
+            None => bail!("Unknown alias '@{name}'"),
+        };
+        match args.trim_start() {
+            "" => Ok(aliased),
+            args => Ok(format!("{aliased} {args}")),
+        }
+    }
+
+    /// Environment variables describing the book, the chapter and the directive, for commands.
+    /// Only the known values are set.
+    pub fn env(&self) -> Vec<(String, String)> {
+        let chapter = self.chapter.as_ref();
+        let vars = [
+            (
+                "CHAPTER_PATH",
+                chapter
+                    .and_then(|c| c.path.as_ref())
+                    .map(|p| p.to_string_lossy().replace('\\', "/")),
+            ),
+            ("CHAPTER_NAME", chapter.map(|c| c.name.clone())),
+            ("SECTION_NUMBER", chapter.and_then(|c| c.section.clone())),
+            ("BOOK_ROOT", self.book_root.as_deref().map(absolute)),
+            ("SRC_DIR", self.src_dir.as_deref().map(absolute)),
+            ("RENDERER", self.renderer.clone()),
+            (
+                "DIRECTIVE_INDEX",
+                self.directive_index.map(|i| i.to_string()),
+            ),
+        ];
+
+        vars.into_iter()
+            .filter_map(|(name, value)| Some((format!("{ENV_PREFIX}{name}"), value?)))
+            .collect()
+    }
+
+    /// Resolve the value of `--cwd`: relative to the working directory,
+    /// or to the book root, the source directory or the repository root with `@root/`, `@src/` or `@repo/`.
+    fn resolve_cwd(&self, cwd: &str) -> Result<PathBuf> {
+        let (prefix, path) = match cwd.split_once(['/', '\\']) {
+            Some((prefix, path)) if prefix.starts_with('@') => (prefix, path),
+            _ if cwd.starts_with('@') => (cwd, ""),
+            _ => return Ok(Path::new(&self.working_dir).join(cwd)),
+        };
+        let book_root = self
+            .book_root
+            .as_ref()
+            .with_context(|| format!("'{prefix}' can only be used when building a book"))?;
+
+        let base = match prefix {
+            "@root" => book_root.clone(),
+            "@src" => self.src_dir.clone().unwrap_or_else(|| book_root.clone()),
+            "@repo" => Self::repo_root(book_root),
+            _ => bail!("Unknown directory '{prefix}', expected @root, @src or @repo"),
+        };
+        Ok(base.join(path))
+    }
+
+    // The closest directory containing the book and a `.git` directory, the book root if none.
+    fn repo_root(book_root: &Path) -> PathBuf {
+        let book_root = dunce::canonicalize(book_root).unwrap_or_else(|_| book_root.to_path_buf());
+        book_root
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .unwrap_or(&book_root)
+            .to_path_buf()
+    }
+}
+
+// Absolute path, for commands which may run in another directory.
+// On Windows, it isn't turned into a `\\?\` path, which cmd can't use as its current directory.
+fn absolute(path: &Path) -> String {
+    dunce::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+impl Outcome {
+    fn success(text: String) -> Outcome {
+        Outcome { text, error: None }
+    }
+
+    fn failure(message: String, text: String) -> Outcome {
+        Outcome {
+            text,
+            error: Some(message),
+        }
+    }
+}
+
+const DEFAULT_DIFF_CONTEXT: usize = 3;
+const ENV_PREFIX: &str = "MDBOOK_CMDRUN_";
+
 cfg_if! {
     if #[cfg(target_family = "unix")] {
         const LAUNCH_SHELL_COMMAND: &str = "sh";
         const LAUNCH_SHELL_FLAG: &str = "-c";
-        const NEWLINE: &str = "\n";
     } else if #[cfg(target_family = "windows")] {
         const LAUNCH_SHELL_COMMAND: &str = "cmd";
         const LAUNCH_SHELL_FLAG: &str = "/C";
-        const NEWLINE: &str = "\r\n";
     }
 }
 
@@ -44,78 +326,92 @@ impl Preprocessor for CmdRun {
         "cmdrun"
     }
 
-    fn supports_renderer(&self, renderer: &str) -> bool {
-        renderer == "html"
+    // Renderers can be restricted with the `renderers` key of mdbook's preprocessor configuration
+    fn supports_renderer(&self, _renderer: &str) -> bool {
+        true
     }
 
-    fn run(&self, _ctx: &PreprocessorContext, mut book: Book) -> Result<Book> {
-        map_chapter(&mut book, &mut CmdRun::run_on_chapter)?;
+    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book> {
+        let config = Config::from_context(ctx)?;
+        let src_dir = ctx.root.join(&ctx.config.book.src);
+        let store = SnapshotStore::new(&ctx.root);
+        let ansi = config.ansi(&ctx.renderer);
+        let book_options = RunOptions {
+            ansi,
+            renderer: Some(ctx.renderer.clone()),
+            book_json: RunOptions::book_json(&book)?,
+            build_dir: Some(ctx.root.join(&ctx.config.build.build_dir)),
+            ..RunOptions::for_book(&config, &ctx.root, &src_dir)?
+        };
+
+        map_chapter(&mut book, &mut |chapter| {
+            let options = book_options.for_chapter(&config, chapter);
+            if config.frozen {
+                CmdRun::run_frozen_on_chapter(chapter, &store, &options)
+            } else {
+                chapter.content = CmdRun::run_on_content_with(&chapter.content, &options)?;
+                Ok(())
+            }
+        })?;
 
         Ok(book)
     }
 }
 
-lazy_static! {
-    static ref SRC_DIR: String = get_src_dir();
-}
-
-#[derive(Deserialize)]
-struct BookConfig {
-    book: BookField,
-}
+impl CmdRun {
+    // Replace every directive by its recorded snapshot, without running anything.
+    // The snapshot of a directive is the one recorded for the renderer of `options`.
+    fn run_frozen_on_chapter(
+        chapter: &mut Chapter,
+        store: &SnapshotStore,
+        options: &RunOptions,
+    ) -> Result<()> {
+        let path = match &chapter.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
 
-#[derive(Deserialize)]
-struct BookField {
-    src: Option<String>,
-}
+        chapter.content = Self::replace_directives(&chapter.content, |index, command, _| {
+            let variant = Self::snapshot_variant(&command, options);
+            let snapshot = store.read(path, index, variant)?.with_context(|| {
+                format!(
+                    "Missing snapshot {} for 'cmdrun {}' in chapter '{}', record it with 'mdbook-cmdrun check --update-snapshots'",
+                    store.path(path, index, variant).display(),
+                    command.trim(),
+                    chapter.name
+                )
+            })?;
+            Ok(options.ansi.apply(snapshot))
+        })?;
 
-fn get_src_dir() -> String {
-    fs::read_to_string(Path::new("book.toml"))
-        .map_err(|_| None::<String>)
-        .and_then(|fc| toml::from_str::<BookConfig>(fc.as_str()).map_err(|_| None))
-        .and_then(|bc| bc.book.src.ok_or(None))
-        .unwrap_or_else(|_| String::from("src"))
-}
+        Ok(())
+    }
 
-impl CmdRun {
-    fn run_on_chapter(chapter: &mut Chapter) -> Result<()> {
-        let working_dir = &chapter
+    /// Directory in which the directives of a chapter are run,
+    /// i.e. the directory of the chapter's source file.
+    pub fn chapter_working_dir(src_dir: &Path, chapter: &Chapter) -> String {
+        chapter
             .path
             .to_owned()
-            .and_then(|p| {
-                Path::new(SRC_DIR.as_str())
-                    .join(p)
-                    .parent()
-                    .map(PathBuf::from)
-            })
+            .and_then(|p| src_dir.join(p).parent().map(PathBuf::from))
             .and_then(|p| p.to_str().map(String::from))
-            .unwrap_or_default();
-
-        chapter.content = CmdRun::run_on_content(&chapter.content, working_dir)?;
-
-        Ok(())
+            .unwrap_or_default()
     }
 
-    // This method is public for regression tests
-    pub fn run_on_content(content: &str, working_dir: &str) -> Result<String> {
+    // Replace every directive of the content by the result of `func`,
+    // called with the position of the directive (starting at 1), its command and whether it is inline.
+    fn replace_directives<F>(content: &str, mut func: F) -> Result<String>
+    where
+        F: FnMut(usize, String, bool) -> Result<String>,
+    {
         let mut err = None;
+        let mut index = 0;
 
-        let mut result = CMDRUN_REG_NEWLINE
+        let result = CMDRUN_REG
             .replace_all(content, |caps: &Captures| {
-                Self::run_cmdrun(caps[1].to_string(), working_dir, false).unwrap_or_else(|e| {
-                    err = Some(e);
-                    String::new()
-                })
-            })
-            .to_string();
-
-        if let Some(e) = err {
-            return Err(e);
-        }
-
-        result = CMDRUN_REG_INLINE
-            .replace_all(result.as_str(), |caps: &Captures| {
-                Self::run_cmdrun(caps[1].to_string(), working_dir, true).unwrap_or_else(|e| {
+                index += 1;
+                let inline = caps.get(2).is_none();
+                func(index, caps[1].to_string(), inline).unwrap_or_else(|e| {
                     err = Some(e);
                     String::new()
                 })
@@ -128,154 +424,441 @@ impl CmdRun {
         }
     }
 
-    // Some progams output linebreaks in UNIX format,
-    // this can cause problems on Windows if for any reason
-    // the user is expecting consistent linebreaks,
-    // e.g. they run the resulting markdown through a validation tool.
-    //
-    // So this function simply replaces all linebreaks with Windows linebreaks.
-    #[cfg(target_family = "windows")]
-    fn format_whitespace(str: Cow<'_, str>, inline: bool) -> String {
-        let str = match inline {
-            // When running inline it is undeseriable to have trailing whitespace
-            true => str.trim_end(),
-            false => str.as_ref(),
+    // This method is public for regression tests
+    pub fn run_on_content(content: &str, working_dir: &str) -> Result<String> {
+        let options = RunOptions {
+            working_dir: working_dir.to_string(),
+            ..Default::default()
         };
+        Self::run_on_content_with(content, &options)
+    }
+
+    pub fn run_on_content_with(content: &str, options: &RunOptions) -> Result<String> {
+        Self::replace_directives(content, |index, command, inline| {
+            Ok(Self::run_directive(command, inline, &options.for_directive(index))?.text)
+        })
+    }
+
+    /// List the directives of some content, in order of appearance,
+    /// as `(command, inline)` pairs.
+    pub fn directives(content: &str) -> Vec<(String, bool)> {
+        CMDRUN_REG
+            .captures_iter(content)
+            .map(|caps| (caps[1].to_string(), caps.get(2).is_none()))
+            .collect()
+    }
+
+    /// Snapshot of a directive used for the renderer of `options`, `None` for the main one.
+    /// Directives which can't be parsed only have their main snapshot.
+    pub fn snapshot_variant(text: &str, options: &RunOptions) -> Option<&'static str> {
+        vars::interpolate(text, |name| options.var(name))
+            .and_then(|directive| Directive::parse(&directive))
+            .ok()?
+            .snapshot_variant(options.renderer.as_deref())
+    }
 
-        let mut res = str.lines().collect::<Vec<_>>().join("\r\n");
-        if !inline && !res.is_empty() {
-            res.push_str("\r\n");
+    // Whether a directive of some content uses a flag, so that its input is only prepared when needed.
+    fn uses_flag(content: &str, flag: &str) -> bool {
+        CMDRUN_REG
+            .captures_iter(content)
+            .any(|caps| caps[1].contains(flag))
+    }
+
+    // Output of the built-in replacing the command, if any.
+    fn run_builtin(directive: &Directive, options: &RunOptions) -> Option<Result<builtin::Output>> {
+        let working_dir = Path::new(&options.working_dir);
+
+        if let Some(spec) = &directive.include {
+            Some(builtin::include(spec, working_dir).map(builtin::Output::Text))
+        } else if let Some((old, new)) = &directive.diff {
+            let context = directive.context.unwrap_or(DEFAULT_DIFF_CONTEXT);
+            // Only the html renderer can display a table, it is also used when not rendering
+            let html = options.renderer.as_deref().unwrap_or("html") == "html";
+
+            if directive.side_by_side && html {
+                Some(
+                    builtin::diff_side_by_side(old, new, working_dir, context)
+                        .map(builtin::Output::Html),
+                )
+            } else {
+                Some(builtin::diff(old, new, working_dir, context).map(builtin::Output::Text))
+            }
+        } else {
+            None
         }
+    }
 
-        return res;
+    // Turn the raw output of a directive into the text inserted in the chapter.
+    fn format_output(
+        output: String,
+        inline: bool,
+        directive: &Directive,
+        options: &RunOptions,
+    ) -> Result<String> {
+        let escape = directive.escape.unwrap_or(options.escape);
+        let mut output = output;
+        for step in &directive.steps {
+            output = step.apply(output);
+        }
+        output = options.normalize.content(output, directive.sort);
+        // Escaping comes before ANSI codes are turned into HTML, and `<pre>` elements after
+        output = if directive.format == Some(Format::Table) {
+            let table = table::to_table(
+                &output,
+                directive.columns.as_deref(),
+                &directive.align,
+                escape,
+            )?;
+            options.ansi.apply(table)
+        } else {
+            escape.wrap(options.ansi.apply(escape.text(&output)), inline)
+        };
+        Ok(options.normalize.whitespace(&output, inline))
     }
 
-    #[cfg(target_family = "unix")]
-    fn format_whitespace(str: Cow<'_, str>, inline: bool) -> String {
-        match inline {
-            // Wh;n running inline it is undeseriable to have trailing whitespace
-            true => str.trim_end().to_string(),
-            false => str.to_string(),
+    fn output_outcome(
+        output: String,
+        inline: bool,
+        directive: &Directive,
+        options: &RunOptions,
+        command: &str,
+    ) -> Outcome {
+        match Self::format_output(output, inline, directive, options) {
+            Ok(text) => Outcome::success(text),
+            Err(e) => Self::cmdrun_error_message(&format!("{e:#}"), command, options),
+        }
+    }
+
+    // JSON given to the command on its standard input, if any.
+    fn stdin(directive: &Directive, options: &RunOptions) -> Result<Option<Arc<str>>> {
+        let (flag, json) = if directive.with_book_json {
+            ("--with-book-json", options.book_json.as_ref())
+        } else if directive.with_chapter_json {
+            (
+                "--with-chapter-json",
+                options.chapter.as_ref().and_then(|c| c.json.as_ref()),
+            )
+        } else {
+            return Ok(None);
+        };
+
+        json.cloned()
+            .map(Some)
+            .with_context(|| format!("'{flag}' can only be used when building a book"))
+    }
+
+    // Run a command, writing `input` to its standard input.
+    fn output_with_stdin(command: &mut Command, input: &str) -> io::Result<Output> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("Standard input is piped");
+
+        // Written from another thread, so that a large input doesn't block while the output is full.
+        // Commands may not read their whole input, failing to write it is not an error.
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                let _ = stdin.write_all(input.as_bytes());
+            });
+            child.wait_with_output()
+        })
+    }
+
+    // Copy a working directory to a new temporary directory.
+    // Repositories, cargo targets and the built book are left out, they are large and commands have no reason to change them.
+    fn isolate(working_dir: &Path, build_dir: Option<&Path>) -> Result<TempDir> {
+        let dir = TempDir::with_prefix("mdbook-cmdrun-")
+            .context("Unable to create a temporary directory")?;
+        utils::fs::copy_dir(working_dir, dir.path(), &|path| {
+            path.file_name()
+                .is_some_and(|name| name == ".git" || name == "target")
+                || build_dir.is_some_and(|build_dir| path == build_dir)
+        })?;
+        Ok(dir)
+    }
+
+    // Text made of a single line, which ends with a newline when not inline.
+    fn line_output(mut text: String, inline: bool, options: &RunOptions) -> String {
+        if !inline {
+            text.push('\n');
+        }
+        options.normalize.whitespace(&text, inline)
+    }
+
+    // What a successful command produced: its artifact, or its output which may be binary data.
+    // `run_dir` is the directory the command was run in, which differs from the working directory when isolated.
+    fn command_outcome(
+        stdout: &[u8],
+        run_dir: &Path,
+        inline: bool,
+        directive: &Directive,
+        options: &RunOptions,
+        command: &str,
+    ) -> Outcome {
+        let working_dir = Path::new(&options.working_dir);
+        if run_dir != working_dir {
+            for path in &directive.keep {
+                let kept = fs::read(run_dir.join(path))
+                    .with_context(|| format!("Unable to read kept file {path}"))
+                    .and_then(|content| {
+                        utils::fs::write_if_changed(&working_dir.join(path), &content)
+                    });
+                if let Err(e) = kept {
+                    return Self::cmdrun_error_message(&format!("{e:#}"), command, options);
+                }
+            }
+        }
+
+        if let Some(path) = &directive.artifact {
+            let embedded = artifact::embed(
+                path,
+                run_dir,
+                directive.embed.unwrap_or_default(),
+                directive.alt.as_deref(),
+                options.artifact_dir.as_ref(),
+                options.directive_index,
+            );
+            return match embedded {
+                Ok(text) => Outcome::success(Self::line_output(text, inline, options)),
+                Err(e) => Self::cmdrun_error_message(&format!("{e:#}"), command, options),
+            };
+        }
+        if directive.binary == Binary::DataUri {
+            let uri = encoding::data_uri(stdout);
+            return Outcome::success(Self::line_output(uri, inline, options));
+        }
+
+        let decoded = if directive.strict_encoding || options.strict_encoding {
+            directive.encoding.decode(stdout)
+        } else if directive.binary == Binary::Error {
+            directive
+                .encoding
+                .check_binary(stdout)
+                .map(|()| directive.encoding.decode_lossy(stdout))
+        } else {
+            Ok(directive.encoding.decode_lossy(stdout))
+        };
+        match decoded {
+            Ok(stdout) => Self::output_outcome(
+                options.redaction.apply(stdout),
+                inline,
+                directive,
+                options,
+                command,
+            ),
+            Err(e) => Self::cmdrun_error_message(&format!("{e:#}"), command, options),
         }
     }
 
-    fn cmdrun_error_message(message: &str, command: &str) -> String {
-        format!("**cmdrun error**: {} in 'cmdrun {}'", message, command)
+    // Errors of cmdrun itself are redacted like the output of commands, they may contain host paths.
+    fn cmdrun_error_message(message: &str, command: &str, options: &RunOptions) -> Outcome {
+        let message = options.redaction.apply(message.to_string());
+        Outcome::failure(
+            message.clone(),
+            format!("**cmdrun error**: {} in 'cmdrun {}'", message, command),
+        )
     }
 
     // This method is public for unit tests
     pub fn run_cmdrun(command: String, working_dir: &str, inline: bool) -> Result<String> {
-        // unfortunately, we need to manually parse the command string for cmdrun's
-        // exit status checking flags.
-        // Some experimentation using clap was done; however, splitting and then re-escaping
-        // the shellwords was found to be a large barrier to using this other tool.
-        let (command, correct_exit_code): (String, Option<i32>) =
-            if let Some(first_word) = command.split_whitespace().next() {
-                if first_word.starts_with('-') {
-                    if first_word.starts_with("--") {
-                        // double-tick long form
-                        match first_word {
-                            "--strict" => (
-                                command
-                                    .split_whitespace()
-                                    .skip(1)
-                                    .collect::<Vec<&str>>()
-                                    .join(" "),
-                                Some(0),
-                            ),
-                            "--expect-return-code" => {
-                                if let Some(second_word) = command.split_whitespace().nth(1) {
-                                    match second_word.parse::<i32>() {
-                                        Ok(return_code) => (
-                                            command
-                                                .split_whitespace()
-                                                .skip(2)
-                                                .collect::<Vec<&str>>()
-                                                .join(" "),
-                                            Some(return_code),
-                                        ),
-                                        Err(_) => {
-                                            return Ok(Self::cmdrun_error_message(
-                                                "No return code after '--expect-return-code'",
-                                                &command,
-                                            ));
-                                        }
-                                    }
-                                } else {
-                                    // no second word after return code, print error
-                                    return Ok(Self::cmdrun_error_message(
-                                        "No return code after '--expect-return-code'",
-                                        &command,
-                                    ));
-                                }
-                            }
-                            some_other_word => {
-                                // unrecognized flag, print error
-                                return Ok(Self::cmdrun_error_message(
-                                    &format!("Unrecognized cmdrun flag {}", some_other_word),
-                                    &command,
-                                ));
-                            }
-                        }
-                    } else {
-                        // single-tick short form
-                        let (_, exit_code) = first_word.rsplit_once('-').unwrap_or(("", "0"));
-                        match exit_code.parse::<i32>() {
-                            Ok(return_code) => (
-                                command
-                                    .split_whitespace()
-                                    .skip(1)
-                                    .collect::<Vec<&str>>()
-                                    .join(" "),
-                                Some(return_code),
-                            ),
-                            Err(_) => {
-                                return Ok(Self::cmdrun_error_message(
-                                    &format!(
-                                        "Unable to interpret short-form exit code {} as a number",
-                                        first_word
-                                    ),
-                                    &command,
-                                ));
-                            }
-                        }
-                    }
-                } else {
-                    (command, None)
+        let options = RunOptions {
+            working_dir: working_dir.to_string(),
+            ..Default::default()
+        };
+        Ok(Self::run_directive(command, inline, &options)?.text)
+    }
+
+    // Process running a command directly: its first word is the program, the other ones its arguments
+    fn exec_process(command: &str) -> Result<Command> {
+        let words = words::split(command)?;
+        let (program, args) = words
+            .split_first()
+            .ok_or_else(|| anyhow!("No program to run"))?;
+        let mut process = Command::new(program);
+        process.args(args);
+        Ok(process)
+    }
+
+    /// Run a single directive.
+    pub fn run_directive(text: String, inline: bool, options: &RunOptions) -> Result<Outcome> {
+        let directive = match vars::interpolate(&text, |name| options.var(name))
+            .and_then(|directive| Directive::parse(&directive))
+            .and_then(|mut directive| {
+                // Without isolation, files written by the command are already in the working directory
+                if !directive.keep.is_empty() && !directive.isolated && !options.isolated {
+                    bail!("'--keep' can only be used along with '--isolated'");
                 }
-            } else {
-                (command, None)
-            };
+                directive.command = options.expand_alias(&directive.command)?;
+                if let Some(command) = &directive.else_command {
+                    directive.else_command = Some(options.expand_alias(command)?);
+                }
+                Ok(directive)
+            }) {
+            Ok(directive) => directive,
+            Err(e) => return Ok(Self::cmdrun_error_message(&e.to_string(), &text, options)),
+        };
+        let cwd_options;
+        let options = match &directive.cwd {
+            None => options,
+            Some(cwd) => match options.resolve_cwd(cwd) {
+                Ok(dir) => {
+                    cwd_options = RunOptions {
+                        working_dir: dir.to_string_lossy().into_owned(),
+                        ..options.clone()
+                    };
+                    &cwd_options
+                }
+                Err(e) => return Ok(Self::cmdrun_error_message(&e.to_string(), &text, options)),
+            },
+        };
+        let command = if directive.is_selected(options.renderer.as_deref()) {
+            if let Some(output) = Self::run_builtin(&directive, options) {
+                return Ok(match output {
+                    Ok(builtin::Output::Text(output)) => {
+                        Self::output_outcome(output, inline, &directive, options, &text)
+                    }
+                    // Already rendered, escaping it would display the markup
+                    Ok(builtin::Output::Html(html)) => {
+                        Outcome::success(options.normalize.whitespace(&html, inline))
+                    }
+                    Err(e) => Self::cmdrun_error_message(&e.to_string(), &text, options),
+                });
+            }
+            // Nothing to run on a platform without a variant nor a default command
+            if directive.command.is_empty() && directive.has_platform_variant() {
+                return Ok(Outcome::success(String::new()));
+            }
+            directive.command.clone()
+        } else {
+            match &directive.else_command {
+                Some(command) => command.clone(),
+                None => return Ok(Outcome::success(String::new())),
+            }
+        };
+        let correct_exit_code = directive
+            .exit_code
+            .clone()
+            .or_else(|| options.exit_code.map(ExitCodes::code));
+        let escape = directive.escape.unwrap_or(options.escape);
 
-        let output = Command::new(LAUNCH_SHELL_COMMAND)
-            .args([LAUNCH_SHELL_FLAG, &command])
-            .current_dir(working_dir)
-            .output()
-            .with_context(|| "Fail to run shell")?;
-
-        let stdout = Self::format_whitespace(String::from_utf8_lossy(&output.stdout), inline);
-        match (output.status.code(), correct_exit_code) {
-            (None, _) => Ok(Self::cmdrun_error_message(
-                "Command was ended before completing",
-                &command,
-            )),
-            (Some(code), Some(correct_code)) => {
-                if code != correct_code {
-                    Ok(format!(
-                        "**cmdrun error**: '{command}' returned exit code {code} instead of {correct_code}.{0}{1}{0}{2}",
-                        NEWLINE,
-                        String::from_utf8_lossy(&output.stdout),
-                        String::from_utf8_lossy(&output.stderr)))
-                } else {
-                    Ok(stdout)
+        let stdin = match Self::stdin(&directive, options) {
+            Ok(stdin) => stdin,
+            Err(e) => return Ok(Self::cmdrun_error_message(&e.to_string(), &text, options)),
+        };
+        if !Path::new(&options.working_dir).is_dir() {
+            return Ok(Self::cmdrun_error_message(
+                &format!("Working directory {} doesn't exist", options.working_dir),
+                &text,
+                options,
+            ));
+        }
+
+        // Side effects of isolated commands are discarded along with the temporary directory
+        let isolated_dir = match directive.isolated || options.isolated {
+            true => match Self::isolate(
+                Path::new(&options.working_dir),
+                options.build_dir.as_deref(),
+            ) {
+                Ok(dir) => Some(dir),
+                Err(e) => {
+                    return Ok(Self::cmdrun_error_message(
+                        &format!("{e:#}"),
+                        &text,
+                        options,
+                    ))
                 }
+            },
+            false => None,
+        };
+        let run_dir = isolated_dir
+            .as_ref()
+            .map_or(Path::new(&options.working_dir), TempDir::path);
+
+        let exec = directive.exec.unwrap_or(options.exec);
+        let mut process = match exec {
+            true => match Self::exec_process(&command) {
+                Ok(process) => process,
+                Err(e) => return Ok(Self::cmdrun_error_message(&e.to_string(), &text, options)),
+            },
+            false => {
+                let mut process = Command::new(LAUNCH_SHELL_COMMAND);
+                process.args([LAUNCH_SHELL_FLAG, &command]);
+                process
             }
-            (Some(_code), None) => {
-                // no correct code specified, program exited with some code _code
-                // could put default check requiring code to be zero here but
-                // that would break current behavior
-                Ok(stdout)
+        };
+        process.envs(options.env()).current_dir(run_dir);
+        let output = match stdin {
+            Some(input) => Self::output_with_stdin(&mut process, &input),
+            None => process.output(),
+        };
+        let output = match output {
+            Ok(output) => output,
+            // Without a shell, the program itself may not exist
+            Err(e) if exec => {
+                let program = process.get_program().to_string_lossy();
+                return Ok(Self::cmdrun_error_message(
+                    &format!("Unable to run '{program}': {e}"),
+                    &text,
+                    options,
+                ));
             }
+            Err(e) => return Err(e).with_context(|| "Fail to run shell"),
+        };
+
+        let redact = |bytes: &[u8]| {
+            options
+                .redaction
+                .apply(directive.encoding.decode_lossy(bytes))
+        };
+        let failure = match (output.status.code(), correct_exit_code) {
+            (None, _) => match exit_code::signal(&output.status) {
+                Some(signal) => Some(format!("'{command}' was terminated by {signal}")),
+                None => {
+                    return Ok(Self::cmdrun_error_message(
+                        "Command was ended before completing",
+                        &command,
+                        options,
+                    ))
+                }
+            },
+            (Some(code), Some(correct_codes)) if !correct_codes.contains(code) => Some(format!(
+                "'{command}' returned exit code {code} instead of {correct_codes}"
+            )),
+            // no correct code specified, by the directive nor the configuration
+            _ => None,
+        };
+        let failure = failure.or_else(|| {
+            let stdout = directive.encoding.decode_lossy(&output.stdout);
+            directive
+                .expectations
+                .iter()
+                .find_map(|expectation| expectation.check(&stdout).err())
+                .map(|reason| format!("'{command}' {reason}"))
+        });
+
+        match failure {
+            Some(message) => {
+                let text = format!(
+                    "**cmdrun error**: {message}.\n{}\n{}",
+                    escape.text(&redact(&output.stdout)),
+                    escape.text(&redact(&output.stderr))
+                );
+                let text = options.ansi.apply(text);
+                Ok(Outcome::failure(
+                    message,
+                    options.normalize.whitespace(&text, false),
+                ))
+            }
+            None => Ok(Self::command_outcome(
+                &output.stdout,
+                run_dir,
+                inline,
+                &directive,
+                options,
+                &text,
+            )),
         }
     }
 }
@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use anyhow::bail;
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::Value;
+
+use mdbook::preprocess::PreprocessorContext;
+
+use crate::normalize::LineEndings;
+use crate::redact::Redaction;
+use crate::utils::ansi;
+use crate::utils::text;
+
+/// Settings of the `[preprocessor.cmdrun]` table of `book.toml`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// Render directives from their recorded snapshots instead of running them.
+    pub frozen: bool,
+    /// What to do with ANSI escape codes, for every renderer.
+    pub ansi: Option<Ansi>,
+    /// How the output of commands is escaped, when directives don't use `--escape`.
+    pub escape: Escape,
+    /// Settings specific to a renderer, from `[preprocessor.cmdrun.renderer.<name>]` tables.
+    pub renderer: HashMap<String, RendererConfig>,
+    /// Redaction of the output of commands, disabled if `None`.
+    pub redact: Option<RedactConfig>,
+    /// Normalization of the output of commands, from the `[preprocessor.cmdrun.normalize]` table.
+    pub normalize: NormalizeConfig,
+    /// Variables interpolated in directives as `{{name}}`, from the `[preprocessor.cmdrun.vars]` table.
+    pub vars: BTreeMap<String, Value>,
+    /// Commands run as `@name`, from the `[preprocessor.cmdrun.aliases]` table.
+    pub aliases: BTreeMap<String, String>,
+    /// Exit code of the directives without an exit code flag.
+    pub default_exit_code: DefaultExitCode,
+    /// Directory in which commands are run, when directives don't use `--cwd`.
+    pub working_dir: WorkingDir,
+    /// Run every command in a temporary copy of its working directory, as with `--isolated`.
+    pub isolated: bool,
+    /// Run every command directly rather than through a shell, as with `--exec`.
+    pub exec: bool,
+    /// Report output which isn't valid text as an error, as with `--strict-encoding`.
+    pub strict_encoding: bool,
+    /// Directory of the book sources where `--artifact` files are copied.
+    pub artifact_dir: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RendererConfig {
+    pub ansi: Option<Ansi>,
+}
+
+/// Settings of the `[preprocessor.cmdrun.redact]` table of `book.toml`.
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RedactConfig {
+    /// Replace the home directory by `~`.
+    pub home: bool,
+    /// Replace the name of the current user by `[USER]`, everywhere it appears as a word.
+    /// Off by default, as CI users have names like `runner` or `build`.
+    pub user: bool,
+    /// Replace the name of the machine by `[HOSTNAME]`.
+    pub hostname: bool,
+    /// Environment variables whose values are replaced by the placeholder.
+    pub env: Vec<String>,
+    /// Regexes whose matches are replaced by the placeholder.
+    pub patterns: Vec<String>,
+    /// Text replacing the values of `env` and the matches of `patterns`.
+    pub placeholder: String,
+}
+
+impl Default for RedactConfig {
+    fn default() -> RedactConfig {
+        RedactConfig {
+            home: true,
+            user: false,
+            hostname: true,
+            env: vec![],
+            patterns: vec![],
+            placeholder: String::from("[REDACTED]"),
+        }
+    }
+}
+
+/// Settings of the `[preprocessor.cmdrun.normalize]` table of `book.toml`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct NormalizeConfig {
+    /// Remove the whitespaces at the end of every line.
+    pub trailing_whitespace: bool,
+    /// Line endings of the output, those of the platform by default.
+    pub line_endings: LineEndings,
+    /// Replace the absolute paths of the book root by relative paths.
+    pub paths: bool,
+    /// Sort the lines of every output.
+    pub sort: bool,
+}
+
+/// Exit code of the directives without an exit code flag: a code, or `"any"`.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(try_from = "RawExitCode")]
+pub enum DefaultExitCode {
+    /// Commands may return any code, their output is inserted anyway.
+    #[default]
+    Any,
+    Code(i32),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawExitCode {
+    Code(i32),
+    Keyword(String),
+}
+
+impl TryFrom<RawExitCode> for DefaultExitCode {
+    type Error = String;
+
+    fn try_from(raw: RawExitCode) -> Result<DefaultExitCode, String> {
+        match raw {
+            RawExitCode::Code(code) => Ok(DefaultExitCode::Code(code)),
+            RawExitCode::Keyword(keyword) if keyword == "any" => Ok(DefaultExitCode::Any),
+            RawExitCode::Keyword(keyword) => Err(format!(
+                "invalid default-exit-code '{keyword}', expected an exit code or \"any\""
+            )),
+        }
+    }
+}
+
+impl DefaultExitCode {
+    pub fn code(self) -> Option<i32> {
+        match self {
+            DefaultExitCode::Any => None,
+            DefaultExitCode::Code(code) => Some(code),
+        }
+    }
+}
+
+/// Directory in which commands are run.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WorkingDir {
+    /// The directory of the chapter's source file.
+    #[default]
+    Chapter,
+    BookRoot,
+    /// The source directory of the book.
+    Src,
+    /// The closest directory containing the book and a `.git` directory.
+    RepoRoot,
+}
+
+/// What to do with ANSI escape codes (colors, ...) in the output of commands.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Ansi {
+    /// Insert them as is.
+    #[default]
+    Keep,
+    /// Remove them, leaving plain text.
+    Strip,
+    /// Turn colors and text attributes into HTML `<span>` elements.
+    Html,
+}
+
+/// How the output of commands is escaped before being inserted in the chapter.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Escape {
+    /// Insert it as is, HTML and markdown included.
+    #[default]
+    None,
+    /// Escape the characters having a meaning in HTML.
+    Html,
+    /// Escape markdown syntax, HTML tags included.
+    Markdown,
+    /// Escape HTML and wrap the output in a `<pre>` element.
+    Pre,
+}
+
+impl Config {
+    pub fn from_context(ctx: &PreprocessorContext) -> Result<Config> {
+        Self::from_book_config(&ctx.config)
+    }
+
+    pub fn from_book_config(config: &mdbook::Config) -> Result<Config> {
+        Ok(config
+            .get_deserialized_opt("preprocessor.cmdrun")?
+            .unwrap_or_default())
+    }
+
+    /// Values of the variables, as they are interpolated.
+    pub fn vars(&self) -> BTreeMap<String, String> {
+        self.vars
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                (name.clone(), value)
+            })
+            .collect()
+    }
+
+    pub fn artifact_dir(&self) -> &str {
+        self.artifact_dir.as_deref().unwrap_or("cmdrun-artifacts")
+    }
+
+    /// Redaction rules of the output of commands, none if redaction is disabled.
+    pub fn redaction(&self) -> Result<Redaction> {
+        match &self.redact {
+            Some(redact) => Redaction::new(redact),
+            None => Ok(Redaction::default()),
+        }
+    }
+
+    /// ANSI handling for a renderer: the renderer setting if any, then the global one.
+    /// By default, escape codes are kept as they are.
+    pub fn ansi(&self, renderer: &str) -> Ansi {
+        self.renderer
+            .get(renderer)
+            .and_then(|r| r.ansi)
+            .or(self.ansi)
+            .unwrap_or_default()
+    }
+}
+
+impl Ansi {
+    pub fn apply(self, str: String) -> String {
+        match self {
+            Ansi::Keep => str,
+            Ansi::Strip => ansi::strip(&str),
+            Ansi::Html => ansi::to_html(&str),
+        }
+    }
+}
+
+impl Escape {
+    pub fn parse(escape: &str) -> Result<Escape> {
+        match escape {
+            "none" => Ok(Escape::None),
+            "html" => Ok(Escape::Html),
+            "markdown" => Ok(Escape::Markdown),
+            "pre" => Ok(Escape::Pre),
+            _ => bail!("Unknown escape mode '{escape}', expected none, html, markdown or pre"),
+        }
+    }
+
+    /// Escape some text, ANSI escape codes are kept so that they can be rendered afterwards.
+    pub fn text(self, str: &str) -> String {
+        match self {
+            Escape::None => str.to_string(),
+            Escape::Html | Escape::Pre => ansi::map_text(str, text::escape_html),
+            Escape::Markdown => ansi::map_text(str, text::escape_markdown),
+        }
+    }
+
+    /// Wrap some escaped text in a `<pre>` element if needed.
+    pub fn wrap(self, str: String, inline: bool) -> String {
+        match self {
+            Escape::Pre => format!(
+                "<pre>{}</pre>{}",
+                str.trim_end_matches(['\r', '\n']),
+                if inline { "" } else { "\n" }
+            ),
+            _ => str,
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Result;
+
+use crate::artifact::Embed;
+use crate::config::Escape;
+use crate::encoding::Binary;
+use crate::encoding::Encoding;
+use crate::exit_code::ExitCodes;
+use crate::expect::Expectation;
+use crate::pipeline::Step;
+use crate::table::Align;
+use crate::utils::words::next_word;
+
+/// How the output of a command is rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// JSON or CSV output turned into a markdown table.
+    Table,
+}
+
+/// Content of a `<!-- cmdrun ... -->` directive: the cmdrun flags, then the command.
+#[derive(Debug, Default)]
+pub struct Directive {
+    pub command: String,
+    /// Exit codes the command may return.
+    pub exit_code: Option<ExitCodes>,
+    /// Assertions on the output of the command.
+    pub expectations: Vec<Expectation>,
+    /// Renderers for which the command is run, all of them if `None`.
+    pub renderers: Option<Vec<String>>,
+    /// Command run instead for the other renderers.
+    pub else_command: Option<String>,
+    /// Command run instead on Unix-like systems.
+    pub unix: Option<String>,
+    /// Command run instead on Windows.
+    pub windows: Option<String>,
+    /// File included instead of running a command, see [`crate::builtin::include`].
+    pub include: Option<String>,
+    /// Files compared instead of running a command, as `(old, new)`.
+    pub diff: Option<(String, String)>,
+    /// Number of lines of context around the changes of a diff.
+    pub context: Option<usize>,
+    /// Render a diff as a side by side HTML table, for the html renderer.
+    pub side_by_side: bool,
+    pub format: Option<Format>,
+    /// Columns of a table, all of them if `None`.
+    pub columns: Option<Vec<String>>,
+    /// Alignment of the columns of a table.
+    pub align: Vec<Align>,
+    /// Post-processing steps applied to the output, in order.
+    pub steps: Vec<Step>,
+    /// Sort the lines of the output.
+    pub sort: bool,
+    /// How the output is escaped, the configured default if `None`.
+    pub escape: Option<Escape>,
+    /// Encoding of the output of the command.
+    pub encoding: Encoding,
+    /// Report output which isn't valid text as an error, rather than replacing what can't be decoded.
+    pub strict_encoding: bool,
+    /// What to do when the output is binary data.
+    pub binary: Binary,
+    /// File produced by the command, inserted instead of its output.
+    pub artifact: Option<String>,
+    pub embed: Option<Embed>,
+    /// Alternative text of the artifact.
+    pub alt: Option<String>,
+    /// Directory in which the command is run, relative to the working directory
+    /// unless it starts with `@root/`, `@src/` or `@repo/`.
+    pub cwd: Option<String>,
+    /// Give the book as JSON to the command, on its standard input.
+    pub with_book_json: bool,
+    /// Give the chapter as JSON to the command, on its standard input.
+    pub with_chapter_json: bool,
+    /// Run the command in a temporary copy of the working directory.
+    pub isolated: bool,
+    /// Run the command directly rather than through a shell, the configured default if `None`.
+    pub exec: Option<bool>,
+    /// Files copied back from the temporary directory to the working directory.
+    pub keep: Vec<String>,
+}
+
+impl Directive {
+    // Unfortunately, we need to manually parse the directive for cmdrun's flags.
+    // Some experimentation using clap was done; however, splitting and then re-escaping
+    // the shellwords was found to be a large barrier to using this other tool.
+    // So the flags are read one word at a time, and the command is the rest of the directive, untouched.
+    pub fn parse(directive: &str) -> Result<Directive> {
+        let mut result = Directive::default();
+        let mut rest = directive;
+
+        while rest.trim_start().starts_with('-') {
+            let (word, after) = next_word(rest)?.expect("Directive starts with a flag");
+            rest = after;
+            // `--flag=value` is the same as `--flag value`
+            let (flag, mut attached) = match word.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (word, None),
+            };
+
+            match flag.as_str() {
+                "--" => break,
+                "--strict" => result.exit_code = Some(ExitCodes::code(0)),
+                "--any-exit" => result.exit_code = Some(ExitCodes::Any),
+                "--expect-failure" => result.exit_code = Some(ExitCodes::Failure),
+                "--expect-return-code" => {
+                    // A word which doesn't start like a code is the command
+                    let (codes, after) = Self::value(&flag, &mut attached, rest)
+                        .ok()
+                        .filter(|(codes, _)| {
+                            codes.starts_with(|c: char| c.is_ascii_digit() || c == '-')
+                        })
+                        .ok_or_else(|| anyhow!("No return code after '--expect-return-code'"))?;
+                    result.exit_code = Some(ExitCodes::parse(&codes)?);
+                    rest = after;
+                }
+                "--expect-contains" => {
+                    let (text, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.expectations.push(Expectation::Contains(text));
+                    rest = after;
+                }
+                "--expect-regex" => {
+                    let (pattern, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.expectations.push(Expectation::regex(&pattern)?);
+                    rest = after;
+                }
+                "--expect-lines" => {
+                    let (count, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.expectations.push(Expectation::lines(&count)?);
+                    rest = after;
+                }
+                "--renderer" => {
+                    let (renderers, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.renderers = Some(Self::list(&renderers));
+                    rest = after;
+                }
+                "--else" => {
+                    let (command, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.else_command = Some(command);
+                    rest = after;
+                }
+                "--unix" => {
+                    let (command, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.unix = Some(command);
+                    rest = after;
+                }
+                "--windows" => {
+                    let (command, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.windows = Some(command);
+                    rest = after;
+                }
+                "--include" => {
+                    let (spec, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.include = Some(spec);
+                    rest = after;
+                }
+                "--diff" => {
+                    let (old, after) = Self::value(&flag, &mut attached, rest)?;
+                    let (new, after) = Self::value(&flag, &mut attached, after)?;
+                    result.diff = Some((old, new));
+                    rest = after;
+                }
+                "--context" => {
+                    let (context, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.context = Some(context.parse::<usize>().map_err(|_| {
+                        anyhow!("Unable to interpret context {context} as a number of lines")
+                    })?);
+                    rest = after;
+                }
+                "--side-by-side" => result.side_by_side = true,
+                "--format" => {
+                    let (format, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.format = match format.as_str() {
+                        "table" => Some(Format::Table),
+                        _ => bail!("Unknown format '{format}', expected table"),
+                    };
+                    rest = after;
+                }
+                "--columns" => {
+                    let (columns, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.columns = Some(Self::list(&columns));
+                    rest = after;
+                }
+                "--align" => {
+                    let (align, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.align = align
+                        .split(',')
+                        .map(|a| Align::parse(a.trim()))
+                        .collect::<Result<_>>()?;
+                    rest = after;
+                }
+                "--lines" | "--grep" | "--replace" | "--head" | "--tail" => {
+                    let (value, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.steps.push(match flag.as_str() {
+                        "--lines" => Step::lines(&value)?,
+                        "--grep" => Step::grep(&value)?,
+                        "--replace" => Step::replace(&value)?,
+                        "--head" => Step::Head(Step::count(&flag, &value)?),
+                        _ => Step::Tail(Step::count(&flag, &value)?),
+                    });
+                    rest = after;
+                }
+                "--dedent" => result.steps.push(Step::Dedent),
+                "--sort" => result.sort = true,
+                "--encoding" => {
+                    let (encoding, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.encoding = Encoding::parse(&encoding)?;
+                    rest = after;
+                }
+                "--artifact" => {
+                    let (path, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.artifact = Some(path);
+                    rest = after;
+                }
+                "--embed" => {
+                    let (embed, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.embed = Some(Embed::parse(&embed)?);
+                    rest = after;
+                }
+                "--alt" => {
+                    let (alt, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.alt = Some(alt);
+                    rest = after;
+                }
+                "--cwd" => {
+                    let (cwd, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.cwd = Some(cwd);
+                    rest = after;
+                }
+                "--isolated" => result.isolated = true,
+                "--exec" => result.exec = Some(true),
+                "--shell" => result.exec = Some(false),
+                "--with-book-json" => result.with_book_json = true,
+                "--with-chapter-json" => result.with_chapter_json = true,
+                "--keep" => {
+                    let (paths, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.keep.extend(Self::list(&paths));
+                    rest = after;
+                }
+                "--strict-encoding" => result.strict_encoding = true,
+                "--binary" => {
+                    let (binary, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.binary = Binary::parse(&binary)?;
+                    rest = after;
+                }
+                "--escape" => {
+                    let (escape, after) = Self::value(&flag, &mut attached, rest)?;
+                    result.escape = Some(Escape::parse(&escape)?);
+                    rest = after;
+                }
+                long if long.starts_with("--") => bail!("Unrecognized cmdrun flag {long}"),
+                short => {
+                    // single-tick short form of exit codes
+                    let code = short[1..].parse::<i32>().map_err(|_| {
+                        anyhow!("Unable to interpret short-form exit code {short} as a number")
+                    })?;
+                    result.exit_code = Some(ExitCodes::code(code));
+                }
+            }
+            if attached.is_some() {
+                bail!("'{flag}' doesn't take a value");
+            }
+        }
+
+        if result.else_command.is_some() && result.renderers.is_none() {
+            bail!("'--else' can only be used along with '--renderer'");
+        }
+
+        result.command = rest.trim().to_string();
+        let has_command = !result.command.is_empty() || result.has_platform_variant();
+        if result.include.is_some() && has_command {
+            bail!("'--include' can't be used along with a command");
+        }
+        if result.diff.is_some() && has_command {
+            bail!("'--diff' can't be used along with a command");
+        }
+        if result.diff.is_none() && (result.context.is_some() || result.side_by_side) {
+            bail!("'--context' and '--side-by-side' can only be used along with '--diff'");
+        }
+        if result.format != Some(Format::Table)
+            && (result.columns.is_some() || !result.align.is_empty())
+        {
+            bail!("'--columns' and '--align' can only be used along with '--format table'");
+        }
+        if result.with_book_json && result.with_chapter_json {
+            bail!("'--with-book-json' and '--with-chapter-json' can't be used together");
+        }
+        if result.artifact.is_none() && (result.embed.is_some() || result.alt.is_some()) {
+            bail!("'--embed' and '--alt' can only be used along with '--artifact'");
+        }
+        if !result.expectations.is_empty() && !has_command {
+            bail!("'--expect-contains', '--expect-regex' and '--expect-lines' need a command");
+        }
+        if result.artifact.is_some() && !has_command {
+            bail!("'--artifact' needs a command producing the file");
+        }
+
+        // The command of the current platform replaces the default one, which is run on the others
+        let platform_command = match cfg!(target_family = "windows") {
+            true => &result.windows,
+            false => &result.unix,
+        };
+        if let Some(command) = platform_command {
+            result.command = command.clone();
+        }
+        Ok(result)
+    }
+
+    // Comma-separated list of values
+    fn list(value: &str) -> Vec<String> {
+        value
+            .split(',')
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .collect()
+    }
+
+    // Value of a flag, either attached to it with `=` or the next word
+    fn value<'a>(
+        flag: &str,
+        attached: &mut Option<String>,
+        rest: &'a str,
+    ) -> Result<(String, &'a str)> {
+        match attached.take() {
+            Some(value) => Ok((value, rest)),
+            None => next_word(rest)?.ok_or_else(|| anyhow!("No value after '{flag}'")),
+        }
+    }
+
+    /// Whether the directive has a command specific to Unix or Windows.
+    pub fn has_platform_variant(&self) -> bool {
+        self.unix.is_some() || self.windows.is_some()
+    }
+
+    /// Whether the directive is run for a renderer, rather than its `--else` command.
+    /// When the renderer is unknown, the directive is always run.
+    pub fn is_selected(&self, renderer: Option<&str>) -> bool {
+        match (&self.renderers, renderer) {
+            (Some(renderers), Some(renderer)) => renderers.iter().any(|r| r == renderer),
+            _ => true,
+        }
+    }
+
+    /// Name of the snapshot recording the output of the directive for a renderer, `None` for the main one.
+    /// Outputs differ when the `--else` command runs instead, or when a side by side diff can't be displayed.
+    pub fn snapshot_variant(&self, renderer: Option<&str>) -> Option<&'static str> {
+        if !self.is_selected(renderer) {
+            Some("else")
+        } else if self.side_by_side && renderer.unwrap_or("html") != "html" {
+            Some("text")
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+/// Encoding of the output of a command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1, every byte is a character.
+    Latin1,
+    /// Little endian unless the output starts with a byte order mark, as written by Windows tools.
+    Utf16,
+    Utf16Le,
+    Utf16Be,
+}
+
+/// What to do with binary output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Binary {
+    /// Decode it as text, like any other output.
+    #[default]
+    Text,
+    /// Report an error, to avoid corrupting the chapter.
+    Error,
+    /// Insert the output as a base64 data URI.
+    DataUri,
+}
+
+impl Encoding {
+    pub fn parse(encoding: &str) -> Result<Encoding> {
+        match encoding.to_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "latin1" | "latin-1" | "iso-8859-1" => Ok(Encoding::Latin1),
+            "utf-16" | "utf16" => Ok(Encoding::Utf16),
+            "utf-16le" => Ok(Encoding::Utf16Le),
+            "utf-16be" => Ok(Encoding::Utf16Be),
+            _ => bail!("Unknown encoding '{encoding}', expected utf-8, latin1 or utf-16"),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Latin1 => "Latin-1",
+            Encoding::Utf16 => "UTF-16",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+        }
+    }
+
+    /// Decode the output of a command strictly.
+    /// Binary data (images, ...) and invalid text are errors rather than being silently altered.
+    pub fn decode(self, bytes: &[u8]) -> Result<String> {
+        self.check_binary(bytes)?;
+
+        match self {
+            Encoding::Utf8 => String::from_utf8(bytes.to_vec()).with_context(|| {
+                format!(
+                    "Output is not valid {}, use '--encoding' to set its encoding",
+                    self.name()
+                )
+            }),
+            Encoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+            _ => {
+                let units = self.utf16_units(bytes)?;
+                String::from_utf16(&units)
+                    .with_context(|| format!("Output is not valid {}", self.name()))
+            }
+        }
+    }
+
+    /// Report an error if the output looks like binary data.
+    pub fn check_binary(self, bytes: &[u8]) -> Result<()> {
+        let binary = bytes.contains(&0)
+            || (mime_type(bytes).is_some() && std::str::from_utf8(bytes).is_err());
+        // UTF-16 text is full of null bytes, which are otherwise a sign of binary data
+        if !self.is_utf16() && binary {
+            bail!(
+                "Output looks like binary data ({} bytes), use '--binary data-uri' to insert it as a data URI",
+                bytes.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// Decode some output, replacing what can't be decoded.
+    pub fn decode_lossy(self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+            _ => self
+                .utf16_units(bytes)
+                .map(|units| String::from_utf16_lossy(&units))
+                .unwrap_or_else(|_| String::from_utf8_lossy(bytes).into_owned()),
+        }
+    }
+
+    fn is_utf16(self) -> bool {
+        matches!(
+            self,
+            Encoding::Utf16 | Encoding::Utf16Le | Encoding::Utf16Be
+        )
+    }
+
+    fn utf16_units(self, bytes: &[u8]) -> Result<Vec<u16>> {
+        let (bytes, big_endian) = match (self, bytes) {
+            (Encoding::Utf16, [0xFE, 0xFF, rest @ ..]) => (rest, true),
+            (Encoding::Utf16, [0xFF, 0xFE, rest @ ..]) => (rest, false),
+            (Encoding::Utf16Le, [0xFF, 0xFE, rest @ ..]) => (rest, false),
+            (Encoding::Utf16Be, [0xFE, 0xFF, rest @ ..]) => (rest, true),
+            (encoding, bytes) => (bytes, encoding == Encoding::Utf16Be),
+        };
+        if bytes.len() % 2 != 0 {
+            bail!(
+                "Output is not valid {}, it has an odd number of bytes",
+                self.name()
+            );
+        }
+
+        Ok(bytes
+            .chunks_exact(2)
+            .map(|pair| match big_endian {
+                true => u16::from_be_bytes([pair[0], pair[1]]),
+                false => u16::from_le_bytes([pair[0], pair[1]]),
+            })
+            .collect())
+    }
+}
+
+impl Binary {
+    pub fn parse(binary: &str) -> Result<Binary> {
+        match binary {
+            "text" => Ok(Binary::Text),
+            "error" => Ok(Binary::Error),
+            "data-uri" => Ok(Binary::DataUri),
+            _ => bail!("Unknown binary mode '{binary}', expected text, error or data-uri"),
+        }
+    }
+}
+
+/// Encode some data as a base64 data URI, its type is guessed from its content.
+pub fn data_uri(bytes: &[u8]) -> String {
+    let svg = std::str::from_utf8(bytes).is_ok_and(|text| {
+        let text = text.trim_start();
+        text.starts_with("<svg") || (text.starts_with("<?xml") && text.contains("<svg"))
+    });
+    let mime = match svg {
+        true => "image/svg+xml",
+        false => mime_type(bytes).unwrap_or("application/octet-stream"),
+    };
+    format!("data:{mime};base64,{}", STANDARD.encode(bytes))
+}
+
+// Type of the usual image and document formats, from their first bytes
+fn mime_type(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        [b'%', b'P', b'D', b'F', ..] => Some("application/pdf"),
+        _ => None,
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+use std::process::ExitStatus;
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+/// Exit codes a command is expected to return.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExitCodes {
+    /// Any exit code.
+    Any,
+    /// Any code but 0.
+    Failure,
+    /// Codes in one of these ranges, both ends included.
+    Ranges(Vec<(i32, i32)>),
+}
+
+impl ExitCodes {
+    pub fn code(code: i32) -> ExitCodes {
+        ExitCodes::Ranges(vec![(code, code)])
+    }
+
+    /// Parse a comma-separated list of codes and ranges: `0`, `0,1`, `0..=2` or `0..3`.
+    pub fn parse(codes: &str) -> Result<ExitCodes> {
+        let invalid = || anyhow!("Invalid exit codes '{codes}', expected N, N,M or N..=M");
+        let number = |n: &str| n.trim().parse::<i32>().map_err(|_| invalid());
+
+        let ranges = codes
+            .split(',')
+            .map(|range| {
+                let (start, end) = match range.split_once("..") {
+                    None => (number(range)?, number(range)?),
+                    Some((start, end)) => match end.strip_prefix('=') {
+                        Some(end) => (number(start)?, number(end)?),
+                        None => (
+                            number(start)?,
+                            number(end)?.checked_sub(1).ok_or_else(invalid)?,
+                        ),
+                    },
+                };
+                match start <= end {
+                    true => Ok((start, end)),
+                    false => Err(invalid()),
+                }
+            })
+            .collect::<Result<_>>()?;
+        Ok(ExitCodes::Ranges(ranges))
+    }
+
+    pub fn contains(&self, code: i32) -> bool {
+        match self {
+            ExitCodes::Any => true,
+            ExitCodes::Failure => code != 0,
+            ExitCodes::Ranges(ranges) => ranges
+                .iter()
+                .any(|&(start, end)| (start..=end).contains(&code)),
+        }
+    }
+}
+
+impl fmt::Display for ExitCodes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitCodes::Any => write!(f, "any code"),
+            ExitCodes::Failure => write!(f, "a non-zero code"),
+            ExitCodes::Ranges(ranges) => {
+                for (i, &(start, end)) in ranges.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    match start == end {
+                        true => write!(f, "{start}")?,
+                        false => write!(f, "{start}..={end}")?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Name of the signal that terminated a process, if it was terminated by a signal.
+#[cfg(target_family = "unix")]
+pub fn signal(status: &ExitStatus) -> Option<String> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let signal = status.signal()?;
+    let name = match signal {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        4 => "SIGILL",
+        6 => "SIGABRT",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        14 => "SIGALRM",
+        15 => "SIGTERM",
+        _ => return Some(format!("signal {signal}")),
+    };
+    Some(format!("{name} ({signal})"))
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn signal(_status: &ExitStatus) -> Option<String> {
+    None
+}
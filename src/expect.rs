@@ -0,0 +1,47 @@
+use anyhow::anyhow;
+use anyhow::Result;
+use regex::Regex;
+
+use crate::pipeline::Step;
+
+/// An assertion on the output of a command, checked after it runs.
+#[derive(Clone, Debug)]
+pub enum Expectation {
+    /// The output contains some text.
+    Contains(String),
+    /// The output matches a regex.
+    Regex(Regex),
+    /// The output has a number of lines.
+    Lines(usize),
+}
+
+impl Expectation {
+    pub fn regex(pattern: &str) -> Result<Expectation> {
+        Ok(Expectation::Regex(Step::regex(pattern)?))
+    }
+
+    pub fn lines(count: &str) -> Result<Expectation> {
+        count.parse::<usize>().map(Expectation::Lines).map_err(|_| {
+            anyhow!("Unable to interpret {count} as a number of lines for '--expect-lines'")
+        })
+    }
+
+    /// Check the output of a command, the error tells how it differs from what is expected.
+    pub fn check(&self, output: &str) -> Result<(), String> {
+        match self {
+            Expectation::Contains(text) if !output.contains(text.as_str()) => {
+                Err(format!("output doesn't contain '{text}'"))
+            }
+            Expectation::Regex(regex) if !regex.is_match(output) => {
+                Err(format!("output doesn't match '{}'", regex.as_str()))
+            }
+            Expectation::Lines(count) => match output.lines().count() {
+                actual if actual != *count => {
+                    Err(format!("output has {actual} lines instead of {count}"))
+                }
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+}
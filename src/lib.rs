@@ -145,6 +145,32 @@
 //! - `-N` where `N` is the integer exit code that the command should return.
 //! - `--strict` requires the command to return 0.
 //! - `--expect-return-code N` requires the command to return code `N`.
+//!   It also accepts several codes and ranges, like `--expect-return-code 0,1` for `grep` or `diff`, or `--expect-return-code 0..=2`.
+//! - `--expect-failure` requires the command to return a code other than 0.
+//! - `--any-exit` accepts any code.
+//!
+//! On Unix, a command terminated by a signal is always reported as an error, along with the name of the signal.
+//!
+//! By default, directives without any of these flags accept any exit code.
+//! To require a code from all of them, set `default-exit-code` in the configuration, and use `--any-exit` on the directives that may fail:
+//!
+//! ```toml
+//! [preprocessor.cmdrun]
+//! default-exit-code = 0 # or "any", the default
+//! ```
+//!
+//! The output of the command can be checked as well, so that the book doubles as a test of the commands it documents:
+//! - `--expect-contains TEXT` requires the output to contain `TEXT`.
+//! - `--expect-regex REGEX` requires the output to match `REGEX`, `^` and `$` matching at the beginning and end of every line.
+//! - `--expect-lines N` requires the output to have `N` lines.
+//!
+//! ```markdown
+//! <!-- cmdrun -0 --expect-contains 'Usage:' --expect-regex '^  --verbose' mytool --help -->
+//! ```
+//!
+//! These checks apply to the output as the command writes it, before any post-processing, and failures are reported like wrong exit codes.
+//!
+//! Flag values containing spaces can be quoted, they can also be attached with `=` (`--expect-return-code=1`), and `--` marks the end of the flags.
 //!
 //! Some more examples are implemented, and are used as regression tests. You can find them [here](https://github.com/FauconFan/mdbook-cmdrun/tree/master/tests/regression/).
 //! At the moment of writing, there are examples using:
@@ -155,7 +181,370 @@
 //! - Node
 //! - Rust
 //!
+//! # Platforms
+//!
+//! Commands are run with `sh` on Unix-like systems and `cmd` on Windows, which differ in quoting, separators and builtins.
+//! `--unix` and `--windows` give the command to run on each of them, the command after the flags being run on the other platform, if any:
+//!
+//! ```markdown
+//! <!-- cmdrun --unix 'ls -1 data' --windows 'dir /b data' -->
+//! <!-- cmdrun --windows 'type data\greeting.txt' cat data/greeting.txt -->
+//! ```
+//!
+//! Nothing is inserted on a platform with neither a variant nor a default command.
+//!
+//! # Running without a shell
+//!
+//! With `--exec`, the command is split into words following shell quoting rules, and run directly: the first word is the program, the other ones its arguments.
+//! Pipes, redirections, variables and other shell syntax are given to the program as they are, and the command behaves the same on every platform.
+//!
+//! ```markdown
+//! <!-- cmdrun --exec python3 scripts/report.py "Quarterly sales" --year 2024 -->
+//! ```
+//!
+//! To run every command this way, set `exec` in the configuration, and use `--shell` for the commands that need a shell:
+//!
+//! ```toml
+//! [preprocessor.cmdrun]
+//! exec = true
+//! ```
+//!
+//! Shell builtins, like `echo` or `dir` with `cmd` on Windows, aren't programs and can't be run without a shell.
+//!
+//! # Post-processing
+//!
+//! The output can be transformed before being inserted, without depending on Unix tools.
+//! The following flags are applied in the order they are given:
+//! - `--lines N:M` keeps the lines from `N` to `M` (`N`, `N:` and `:M` also work).
+//! - `--head N` and `--tail N` keep the first or last `N` lines.
+//! - `--grep REGEX` keeps the lines matching the regex.
+//! - `--replace 'REGEX=>TEXT'` replaces every match of the regex, `$1` or `${name}` refer to capture groups.
+//! - `--dedent` removes the indentation shared by all the lines.
+//!
+//! ```markdown
+//! <!-- cmdrun --grep INFO --replace '^\S+ \S+ =>' --tail 10 cat server.log -->
+//! ```
+//!
+//! # Encodings and binary output
+//!
+//! The output of commands is expected to be UTF-8, `--encoding` reads it as `latin1` or `utf-16` instead
+//! (little endian unless there is a byte order mark, `utf-16le` and `utf-16be` also exist):
+//!
+//! ```markdown
+//! <!-- cmdrun --encoding utf-16 powershell -Command Get-Date -->
+//! ```
+//!
+//! What can't be decoded is replaced by `�`.
+//! With `--strict-encoding`, or `strict-encoding = true` in the configuration, output that isn't valid text,
+//! or that looks like binary data (e.g. an image), is reported as an error rather than corrupting the chapter.
+//! `--binary error` only reports binary data.
+//!
+//! With `--binary data-uri`, the output is inserted as a base64 data URI, whose type is guessed from the data (PNG, JPEG, GIF, WebP, SVG, PDF):
+//!
+//! ```markdown
+//! <img src="<!-- cmdrun --binary data-uri python3 plot.py -->" alt="Plot">
+//! ```
+//!
+//! # Generated files
+//!
+//! Some tools write files rather than printing something, e.g. plots or diagrams.
+//! With `--artifact PATH`, the file written by the command is inserted instead of its output: images as images, other files as links.
+//! `--alt` gives the alternative text, the name of the file by default.
+//!
+//! ```markdown
+//! <!-- cmdrun --artifact graph.svg --alt "Dependency graph" dot -Tsvg deps.dot -o graph.svg -->
+//! ```
+//!
+//! `--embed` chooses how the file is inserted:
+//! - `--embed copy` (the default) copies it to the `cmdrun-artifacts/<chapter>` directory of the book sources, so that it is part of the built book.
+//!   Files keep their path relative to the working directory, files outside of it are prefixed by the position of their directive.
+//!   Building the book writes to this directory of the source tree: add it to your `.gitignore`, which also keeps `mdbook serve` from watching it.
+//!   Its name can be changed with `artifact-dir = "generated"` in the configuration.
+//! - `--embed data-uri` references it as a base64 data URI.
+//! - `--embed inline` inserts the content of an SVG file directly in the page.
+//!
+//! # Variables
+//!
+//! Values repeated across directives can be defined once in the configuration, and used as `{{name}}` in directives:
+//!
+//! ```toml
+//! [preprocessor.cmdrun.vars]
+//! example_dir = "../examples"
+//! tool_version = "1.4.2"
+//! ```
+//!
+//! ```markdown
+//! <!-- cmdrun cargo run --manifest-path {{example_dir}}/Cargo.toml -- --version {{tool_version}} -->
+//! ```
+//!
+//! `{{book_root}}` (or `{{root}}`), `{{src_dir}}` and `{{chapter_dir}}` are also available, as absolute paths.
+//! Values containing spaces or other special characters are quoted for the shell, or escaped when the variable is already inside quotes,
+//! so that they are always passed as they are. Unknown variables are reported as errors.
+//! Commands using the same syntax, like templates, can escape it as `\{{name}}`, which is passed as `{{name}}`.
+//!
+//! # Aliases
+//!
+//! Long commands used in several chapters can be named in the configuration, and run as `@name` followed by their arguments:
+//!
+//! ```toml
+//! [preprocessor.cmdrun.aliases]
+//! pytable = "python3 {{book_root}}/tools/table.py"
+//! ```
+//!
+//! ```markdown
+//! <!-- cmdrun @pytable sales.csv -->
+//! <!-- cmdrun --renderer html --else '@pytable --plain sales.csv' @pytable --html sales.csv -->
+//! ```
+//!
+//! Variables can be used in aliases, and flags go before the alias, as with any command.
+//! Unknown aliases are reported as errors.
+//!
+//! # Environment variables
+//!
+//! Commands can tell where they are run from with these environment variables:
+//! - `MDBOOK_CMDRUN_CHAPTER_PATH`: path of the chapter's source file, relative to the source directory, e.g. `nested/chapter.md`.
+//! - `MDBOOK_CMDRUN_CHAPTER_NAME`: name of the chapter.
+//! - `MDBOOK_CMDRUN_SECTION_NUMBER`: section number of the chapter, e.g. `2.1`, unset for unnumbered chapters.
+//! - `MDBOOK_CMDRUN_BOOK_ROOT` and `MDBOOK_CMDRUN_SRC_DIR`: absolute paths of the book root and of its source directory.
+//! - `MDBOOK_CMDRUN_RENDERER`: renderer the book is built for, unset with `mdbook-cmdrun check`.
+//! - `MDBOOK_CMDRUN_DIRECTIVE_INDEX`: position of the directive in its chapter, starting at 1.
+//!
+//! ```markdown
+//! <!-- cmdrun python3 breadcrumb.py -->
+//! ```
+//!
+//! # Book structure on standard input
+//!
+//! With `--with-book-json`, the command reads the whole book as JSON on its standard input, as mdbook gives it to preprocessors.
+//! With `--with-chapter-json`, it reads the current chapter (name, content, number, path, sub-items, ...).
+//! The chapters are given as they are before any directive runs.
+//!
+//! ```markdown
+//! <!-- cmdrun --with-book-json python3 list_api_chapters.py -->
+//! ```
+//!
+//! # Working directory
+//!
+//! Commands run in the directory of their chapter by default.
+//! `--cwd` runs a command in another directory, relative to the chapter, or to the book root, the source directory
+//! or the repository root (the closest directory containing `.git`) with the `@root`, `@src` and `@repo` prefixes:
+//!
+//! ```markdown
+//! <!-- cmdrun --cwd @repo/tools ./list-features.sh -->
+//! <!-- cmdrun --cwd ../examples cargo run --example hello -->
+//! ```
+//!
+//! The default working directory of the whole book can be `chapter`, `book-root`, `src` or `repo-root`:
+//!
+//! ```toml
+//! [preprocessor.cmdrun]
+//! working-dir = "book-root"
+//! ```
+//!
+//! The working directory must exist, otherwise an error is reported.
+//!
+//! # Isolated commands
+//!
+//! Commands run in the directory of their chapter, so files they write end up in the book sources,
+//! which can make `mdbook serve` rebuild the book over and over.
+//! With `--isolated`, a command runs in a temporary copy of this directory, which is removed afterwards along with any change made by the command.
+//! The copy leaves out `.git` and `target` directories as well as the build directory of the book, and symbolic links are copied as links.
+//! `--artifact` files are still inserted, and `--keep` gives a comma-separated list of files copied back to the directory of the chapter:
+//!
+//! ```markdown
+//! <!-- cmdrun --isolated --keep results.csv python3 benchmark.py -->
+//! ```
+//!
+//! To isolate every command, use `isolated = true` in the configuration. `--keep` can't be used without isolation.
+//!
+//! # Reproducible output
+//!
+//! To get the same book whatever the machine building it, the output can be normalized:
+//!
+//! ```toml
+//! [preprocessor.cmdrun.normalize]
+//! # Remove the whitespaces at the end of lines
+//! trailing-whitespace = true
+//! # "lf", "crlf", or "native" (the default) for the line endings of the platform
+//! line-endings = "lf"
+//! # Replace the absolute path of the book root by relative paths, e.g. "/home/me/book/src" by "src"
+//! paths = true
+//! # Sort the lines of every output
+//! sort = true
+//! ```
+//!
+//! The lines of a single output can also be sorted with `--sort`. Timestamps and other varying values can be replaced with `--replace`.
+//!
+//! # Escaping
+//!
+//! The output is inserted as is, so HTML tags and markdown syntax printed by a command end up in the page.
+//! `--escape` changes this:
+//! - `--escape none` inserts the output as is.
+//! - `--escape html` escapes the characters having a meaning in HTML (`<`, `>`, `&`, ...).
+//! - `--escape markdown` escapes markdown syntax, HTML tags included, with backslashes.
+//! - `--escape pre` escapes HTML and wraps the output in a `<pre>` element.
+//!
+//! ```markdown
+//! <!-- cmdrun --escape pre curl -s https://example.com -->
+//! ```
+//!
+//! The default is `none`, it can be changed for the whole book:
+//!
+//! ```toml
+//! [preprocessor.cmdrun]
+//! escape = "html"
+//! ```
+//!
+//! Side by side diffs are HTML generated by cmdrun, they are never escaped.
+//!
+//! # Tables
+//!
+//! With `--format table`, the output of the command is turned into a markdown table.
+//! The output is either a JSON array of objects, whose keys are the columns, or CSV whose first line holds the names of the columns.
+//! `--columns` selects and orders the columns, and `--align` gives the alignment (`left`, `center`, `right` or `none`) of each column,
+//! or of all the columns if a single one is given.
+//!
+//! ```markdown
+//! <!-- cmdrun --format table python3 sales.py -->
+//! <!-- cmdrun --format table --columns product,price --align left,right cat sales.csv -->
+//! ```
+//!
+//! # Including files
+//!
+//! Files can be included without running any command (and so without depending on `cat`), using `--include`.
+//! Like mdbook's `{{#include}}`, a range of lines or an anchor can be selected:
+//!
+//! ````markdown
+//! ```rust
+//! <!-- cmdrun --include program.rs -->
+//! <!-- cmdrun --include program.rs:10 -->
+//! <!-- cmdrun --include program.rs:10:30 -->
+//! <!-- cmdrun --include program.rs:10: -->
+//! <!-- cmdrun --include program.rs::30 -->
+//! <!-- cmdrun --include program.rs:main --dedent -->
+//! ```
+//! ````
+//!
+//! The last one includes the lines between `ANCHOR: main` and `ANCHOR_END: main`.
+//! Lines containing anchor markers are never included.
+//! The path is relative to the directory of the chapter, and `--dedent` removes the indentation shared by all the lines.
+//!
+//! Similarly, `--diff OLD NEW` shows the differences between two files as a unified diff, without depending on `diff`.
+//! By default, three lines of context are shown around each change, use `--context N` to change it.
+//! With `--side-by-side`, the html renderer shows a table with both files side by side instead.
+//!
+//! ````markdown
+//! ```diff
+//! <!-- cmdrun --diff a.rs b.rs -->
+//! ```
+//!
+//! <!-- cmdrun --diff a.rs b.rs --context 1 --side-by-side -->
+//! ````
+//!
+//! # Renderers
+//!
+//! The preprocessor runs for every renderer (html, markdown, epub, pdf, ...).
+//! To restrict it to some of them, use the `renderers` key of mdbook:
+//!
+//! ```toml
+//! [preprocessor.cmdrun]
+//! renderers = ["html", "markdown"]
+//! ```
+//!
+//! ANSI escape codes (e.g. colors) in the output of commands are inserted as they are by default.
+//! They can instead be turned into HTML `<span>` elements with `html`, or removed with `strip`, globally or per renderer:
+//!
+//! ```toml
+//! [preprocessor.cmdrun]
+//! ansi = "strip"
+//!
+//! [preprocessor.cmdrun.renderer.html]
+//! ansi = "html"
+//! ```
+//!
+//! A directive can also be restricted to some renderers with `--renderer`, followed by a comma-separated list of renderers.
+//! For the other renderers, the directive is removed, or replaced by the output of the command given with `--else`:
+//!
+//! ```markdown
+//! <!-- cmdrun --renderer html python3 widget.py -->
+//! <!-- cmdrun --renderer html,epub --else "python3 plot.py --text" python3 plot.py --svg -->
+//! ```
+//!
+//! # Redaction
+//!
+//! Commands can leak secrets or details of the machine building the book into their output.
+//! Adding a `redact` table to the configuration replaces them, in the output and in the error messages of failing commands and of cmdrun itself:
+//!
+//! ```toml
+//! [preprocessor.cmdrun.redact]
+//! # Values of these environment variables
+//! env = ["GITHUB_TOKEN", "API_KEY"]
+//! # Matches of these regexes
+//! patterns = ['sk-[A-Za-z0-9]+']
+//! # Text replacing them, "[REDACTED]" by default
+//! placeholder = "***"
+//! ```
+//!
+//! The home directory, and the paths inside it, are also replaced by `~`, and the name of the machine by `[HOSTNAME]`.
+//! These can be disabled with `home = false` and `hostname = false`.
+//!
+//! With `user = true`, the name of the current user is replaced by `[USER]` wherever it appears as a word.
+//! It is off by default: CI machines often run as users named `runner`, `build` or `root`, which are also common words.
+//!
+//! # Checking a book in CI
+//!
+//! The `check` subcommand runs every directive of a book without rendering it.
+//! It prints a summary of the directives run per chapter, how long they took and which ones failed,
+//! and exits with a non-zero code if any directive failed.
+//!
+//! ```sh
+//! mdbook-cmdrun check path/to/book
+//! ```
+//!
+//! With `--strict`, directives without an exit code flag are also required to return 0, whatever `default-exit-code` is.
+//!
+//! ## Snapshots
+//!
+//! To detect drift in generated documentation, the output of every directive can be recorded
+//! in `.cmdrun-snapshots/<chapter>/<n>.out` next to `book.toml`, `<n>` being the position of the directive in the chapter.
+//!
+//! ```sh
+//! mdbook-cmdrun check --update-snapshots path/to/book
+//! ```
+//!
+//! The snapshots of failing directives are left as they were, and those of directives which no longer exist are removed.
+//! Directives whose output depends on the renderer, because of `--renderer` or `--side-by-side`, get another snapshot
+//! `<n>.else.out` or `<n>.text.out` for the renderers of the book which don't get the main output.
+//!
+//! Then `mdbook-cmdrun check --snapshots path/to/book` fails if the output of a directive differs from its snapshot,
+//! and prints a unified diff of the differences.
+//!
+//! ## Frozen mode
+//!
+//! Once snapshots are recorded, the book can be built on a machine without the tools the commands need.
+//! With the following configuration, no command is run and every directive is replaced by its snapshot for the current renderer.
+//! The build fails if a snapshot is missing.
+//!
+//! ```toml
+//! [preprocessor.cmdrun]
+//! frozen = true
+//! ```
+//!
+pub mod artifact;
+mod builtin;
+pub mod check;
 pub mod cmdrun;
+pub mod config;
+mod directive;
+mod encoding;
+mod exit_code;
+mod expect;
+pub mod normalize;
+mod pipeline;
+pub mod redact;
+pub mod snapshot;
+mod table;
 mod utils;
+mod vars;
 
 pub use cmdrun::CmdRun;
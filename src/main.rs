@@ -1,11 +1,14 @@
-use clap::{Arg, ArgMatches, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use mdbook::errors::Error;
 use mdbook::preprocess::CmdPreprocessor;
 use mdbook::preprocess::Preprocessor;
 
 use std::io;
+use std::path::Path;
 use std::process;
 
+use mdbook_cmdrun::check;
+use mdbook_cmdrun::check::{CheckOptions, SnapshotMode};
 use mdbook_cmdrun::CmdRun;
 
 fn main() {
@@ -13,6 +16,8 @@ fn main() {
 
     if let Some(sub_args) = matches.subcommand_matches("supports") {
         handle_supports(sub_args);
+    } else if let Some(sub_args) = matches.subcommand_matches("check") {
+        handle_check(sub_args);
     } else if let Err(e) = handle_preprocessing() {
         eprintln!("{e}");
         process::exit(1);
@@ -27,6 +32,30 @@ fn make_app() -> Command {
                 .arg(Arg::new("renderer").required(true))
                 .about("Check whether a renderer is supported by this preprocessor"),
         )
+        .subcommand(
+            Command::new("check")
+                .arg(Arg::new("book-dir").default_value("."))
+                .arg(
+                    Arg::new("strict")
+                        .long("strict")
+                        .action(ArgAction::SetTrue)
+                        .help("Require directives without an exit code flag to return 0"),
+                )
+                .arg(
+                    Arg::new("snapshots")
+                        .long("snapshots")
+                        .action(ArgAction::SetTrue)
+                        .help("Compare the output of every directive with its recorded snapshot"),
+                )
+                .arg(
+                    Arg::new("update-snapshots")
+                        .long("update-snapshots")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("snapshots")
+                        .help("Record the output of every directive as its snapshot"),
+                )
+                .about("Run every cmdrun directive of a book and report failures, without rendering it"),
+        )
 }
 
 fn handle_preprocessing() -> Result<(), Error> {
@@ -60,3 +89,30 @@ fn handle_supports(sub_args: &ArgMatches) -> ! {
         process::exit(1);
     }
 }
+
+fn handle_check(sub_args: &ArgMatches) -> ! {
+    let book_dir = sub_args
+        .get_one::<String>("book-dir")
+        .expect("Argument has a default value");
+    let options = CheckOptions {
+        strict: sub_args.get_flag("strict"),
+        snapshots: if sub_args.get_flag("update-snapshots") {
+            SnapshotMode::Update
+        } else if sub_args.get_flag("snapshots") {
+            SnapshotMode::Verify
+        } else {
+            SnapshotMode::Ignore
+        },
+    };
+
+    match check::check(Path::new(book_dir), &options) {
+        Ok(report) => {
+            println!("{report}");
+            process::exit(if report.is_success() { 0 } else { 1 });
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+    }
+}
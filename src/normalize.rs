@@ -0,0 +1,128 @@
+use std::cmp::Reverse;
+use std::path::Path;
+
+use regex::Captures;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::config::NormalizeConfig;
+
+/// Line endings of the text inserted in the chapter.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineEndings {
+    /// `\r\n` on Windows, `\n` elsewhere.
+    #[default]
+    Native,
+    Lf,
+    Crlf,
+}
+
+/// Normalization of the output of commands, so that books are the same whatever the machine building them.
+#[derive(Clone, Debug, Default)]
+pub struct Normalize {
+    /// Remove the whitespaces at the end of every line.
+    pub trailing_whitespace: bool,
+    /// Line endings of every line, the last one included.
+    pub line_endings: LineEndings,
+    /// Sort the lines of every output.
+    pub sort: bool,
+    /// Absolute paths of the book root, replaced by relative paths.
+    roots: Vec<Regex>,
+}
+
+impl Normalize {
+    /// Normalization described by the configuration, for a book located in `book_root`.
+    pub fn new(config: &NormalizeConfig, book_root: &Path) -> Normalize {
+        let mut roots = vec![];
+        if config.paths {
+            let mut paths = vec![book_root.to_path_buf()];
+            // The book root may be relative or go through symbolic links
+            if let Ok(canonical) = dunce::canonicalize(book_root) {
+                paths.push(canonical);
+            }
+            for path in paths.iter().filter(|p| p.is_absolute()) {
+                let path = path.to_string_lossy();
+                let path = path.trim_end_matches(['/', '\\']);
+                let pattern = format!(r"{}(?:[/\\]|\b)", regex::escape(path));
+                if !roots.iter().any(|r: &Regex| r.as_str() == pattern) {
+                    roots.push(Regex::new(&pattern).expect("Escaped path is a valid regex"));
+                }
+            }
+            // Longest paths first, so that one path doesn't cut another one containing it
+            roots.sort_by_key(|r| Reverse(r.as_str().len()));
+        }
+
+        Normalize {
+            trailing_whitespace: config.trailing_whitespace,
+            line_endings: config.line_endings,
+            sort: config.sort,
+            roots,
+        }
+    }
+
+    /// Normalize the content of an output, before it is escaped and formatted.
+    /// `sort` sorts the lines even if it isn't configured.
+    pub fn content(&self, mut output: String, sort: bool) -> String {
+        for root in &self.roots {
+            output = root
+                .replace_all(&output, |caps: &Captures| {
+                    // The root itself becomes `.`, paths inside it become relative
+                    match caps[0].ends_with(['/', '\\']) {
+                        true => "",
+                        false => ".",
+                    }
+                })
+                .to_string();
+        }
+
+        if self.sort || sort {
+            let mut lines = output.lines().collect::<Vec<_>>();
+            lines.sort_unstable();
+            let mut sorted = lines.join("\n");
+            if output.ends_with('\n') {
+                sorted.push('\n');
+            }
+            output = sorted;
+        }
+        output
+    }
+
+    /// Normalize the whitespaces of the text inserted in the chapter, this is the last stage.
+    /// When running inline, trailing whitespaces are always removed.
+    /// On Windows, output which isn't inline always ends with a line ending.
+    pub fn whitespace(&self, output: &str, inline: bool) -> String {
+        let output = match inline {
+            true => output.trim_end(),
+            false => output,
+        };
+        let newline = match self.line_endings {
+            LineEndings::Lf => "\n",
+            LineEndings::Crlf => "\r\n",
+            LineEndings::Native if cfg!(target_family = "windows") => "\r\n",
+            LineEndings::Native => "\n",
+        };
+
+        let mut result = String::with_capacity(output.len());
+        for line in output.split_inclusive('\n') {
+            let content = line.trim_end_matches(['\n', '\r']);
+            let content = match self.trailing_whitespace {
+                true => content.trim_end(),
+                false => content,
+            };
+            result.push_str(content);
+            if line.ends_with('\n') {
+                result.push_str(newline);
+            }
+        }
+        // Otherwise an output without a final newline is glued to the next line of the chapter
+        if cfg!(target_family = "windows")
+            && !inline
+            && !result.is_empty()
+            && !result.ends_with('\n')
+        {
+            result.push_str(newline);
+        }
+        result
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Result;
+use regex::Regex;
+use regex::RegexBuilder;
+
+use crate::utils::text;
+
+/// A post-processing step applied to the output of a directive.
+/// Steps are applied in the order they are given in the directive.
+#[derive(Clone, Debug)]
+pub enum Step {
+    /// Keep the lines from `start` to `end`, both included and starting at 1.
+    Lines(usize, Option<usize>),
+    /// Keep the lines matching a regex.
+    Grep(Regex),
+    /// Replace every match of a regex, `$1` and `${name}` refer to capture groups.
+    Replace(Regex, String),
+    /// Keep the first lines.
+    Head(usize),
+    /// Keep the last lines.
+    Tail(usize),
+    /// Remove the indentation shared by every line.
+    Dedent,
+}
+
+impl Step {
+    /// Parse the value of `--lines`: `N`, `N:M`, `N:` or `:M`.
+    pub fn lines(range: &str) -> Result<Step> {
+        let invalid = || anyhow!("Invalid line range '{range}'");
+        let number = |n: &str| n.parse::<usize>().map_err(|_| invalid());
+
+        let (start, end) = match range.split_once(':') {
+            None => (number(range)?, Some(number(range)?)),
+            Some((start, end)) => (
+                if start.is_empty() { 1 } else { number(start)? },
+                if end.is_empty() {
+                    None
+                } else {
+                    Some(number(end)?)
+                },
+            ),
+        };
+        if start == 0 || end.is_some_and(|end| end < start) {
+            return Err(invalid());
+        }
+        Ok(Step::Lines(start, end))
+    }
+
+    pub fn grep(pattern: &str) -> Result<Step> {
+        Ok(Step::Grep(Self::regex(pattern)?))
+    }
+
+    /// Parse the value of `--replace`: `REGEX=>TEXT`.
+    pub fn replace(value: &str) -> Result<Step> {
+        match value.split_once("=>") {
+            Some((pattern, replacement)) => Ok(Step::Replace(
+                Self::regex(pattern)?,
+                replacement.to_string(),
+            )),
+            None => bail!("Expected 'REGEX=>TEXT' after '--replace', got '{value}'"),
+        }
+    }
+
+    pub fn count(flag: &str, count: &str) -> Result<usize> {
+        count
+            .parse::<usize>()
+            .map_err(|_| anyhow!("Unable to interpret {count} as a number of lines for '{flag}'"))
+    }
+
+    /// Regex of a directive, `^` and `$` match at the beginning and end of every line.
+    pub fn regex(pattern: &str) -> Result<Regex> {
+        RegexBuilder::new(pattern)
+            .multi_line(true)
+            .crlf(true)
+            .build()
+            .map_err(|e| {
+                // Only keep the last line of multi-line parse errors
+                let e = e.to_string();
+                let reason = e.lines().last().unwrap_or_default();
+                anyhow!(
+                    "Invalid regex '{pattern}': {}",
+                    reason.trim_start_matches("error: ")
+                )
+            })
+    }
+
+    pub fn apply(&self, output: String) -> String {
+        let lines = || output.split_inclusive('\n');
+
+        match self {
+            Step::Lines(start, end) => lines()
+                .skip(start - 1)
+                .take(end.map_or(usize::MAX, |end| end - start + 1))
+                .collect(),
+            Step::Grep(regex) => lines()
+                .filter(|l| regex.is_match(l.trim_end_matches(['\n', '\r'])))
+                .collect(),
+            Step::Replace(regex, replacement) => {
+                regex.replace_all(&output, replacement.as_str()).to_string()
+            }
+            Step::Head(count) => lines().take(*count).collect(),
+            Step::Tail(count) => {
+                let total = lines().count();
+                lines().skip(total.saturating_sub(*count)).collect()
+            }
+            Step::Dedent => text::dedent(&output),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::env;
+use std::process::Command;
+
+use anyhow::Context;
+use anyhow::Result;
+use regex::Captures;
+use regex::Regex;
+
+use crate::config::RedactConfig;
+
+/// Replacements applied to the output of commands, so that secrets
+/// and host-specific data don't end up in the book.
+#[derive(Clone, Debug, Default)]
+pub struct Redaction {
+    rules: Vec<(Regex, String)>,
+}
+
+/// Where a literal value is replaced.
+#[derive(Clone, Copy)]
+enum Bounds {
+    Anywhere,
+    Word,
+    /// A path and the paths inside it, not a longer name sharing its beginning.
+    Path,
+}
+
+impl Redaction {
+    pub fn new(config: &RedactConfig) -> Result<Redaction> {
+        Self::with_env(config, |name| env::var(name).ok())
+    }
+
+    /// Redaction reading the environment variables with `env`.
+    pub fn with_env(
+        config: &RedactConfig,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Redaction> {
+        let mut rules = vec![];
+        let mut literal = |value: &str, placeholder: &str, bounds: Bounds| {
+            let value = regex::escape(value);
+            let pattern = match bounds {
+                Bounds::Anywhere => value,
+                Bounds::Word => format!(r"\b{value}\b"),
+                // The character after the path is kept, see `apply`
+                Bounds::Path => format!(r"{value}(?P<keep>[^\w.\-]|$)"),
+            };
+            rules.push((
+                Regex::new(&pattern).expect("Escaped value is a valid regex"),
+                placeholder.to_string(),
+            ));
+        };
+
+        // Secrets first, as they could contain the other values
+        for name in &config.env {
+            if let Some(value) = env(name).filter(|v| !v.is_empty()) {
+                literal(&value, &config.placeholder, Bounds::Anywhere);
+            }
+        }
+        // The home directory usually contains the user name
+        if config.home {
+            if let Some(home) = home_dir(&env) {
+                literal(home.trim_end_matches(['/', '\\']), "~", Bounds::Path);
+            }
+        }
+        if config.user {
+            if let Some(user) = non_empty_var(&env, &["USER", "USERNAME", "LOGNAME"]) {
+                literal(&user, "[USER]", Bounds::Word);
+            }
+        }
+        if config.hostname {
+            if let Some(hostname) = host_name(&env) {
+                literal(&hostname, "[HOSTNAME]", Bounds::Word);
+            }
+        }
+        for pattern in &config.patterns {
+            let regex = Regex::new(pattern)
+                .with_context(|| format!("Invalid redaction pattern '{pattern}'"))?;
+            rules.push((regex, config.placeholder.clone()));
+        }
+
+        Ok(Redaction { rules })
+    }
+
+    pub fn apply(&self, mut str: String) -> String {
+        for (regex, placeholder) in &self.rules {
+            str = regex
+                .replace_all(&str, |caps: &Captures| {
+                    let keep = caps.name("keep").map_or("", |m| m.as_str());
+                    format!("{placeholder}{keep}")
+                })
+                .to_string();
+        }
+        str
+    }
+}
+
+fn non_empty_var(env: impl Fn(&str) -> Option<String>, names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| env(name).filter(|v| !v.is_empty()))
+}
+
+fn home_dir(env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    non_empty_var(env, &["HOME", "USERPROFILE"]).filter(|home| home != "/")
+}
+
+fn host_name(env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    non_empty_var(env, &["HOSTNAME", "COMPUTERNAME"]).or_else(|| {
+        let output = Command::new("hostname").output().ok()?;
+        let hostname = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (output.status.success() && !hostname.is_empty()).then_some(hostname)
+    })
+}
@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use similar::TextDiff;
+
+/// Name of the directory, relative to the book root, where snapshots are stored.
+pub const SNAPSHOT_DIR: &str = ".cmdrun-snapshots";
+
+/// Recorded outputs of directives, stored as
+/// `.cmdrun-snapshots/<chapter>/<n>.out`, where `<chapter>` is the chapter path
+/// relative to the source directory without its extension and `n` is the
+/// position of the directive in the chapter, starting at 1.
+/// Outputs specific to some renderers are stored as `<n>.<variant>.out`.
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(book_root: &Path) -> SnapshotStore {
+        SnapshotStore {
+            dir: book_root.join(SNAPSHOT_DIR),
+        }
+    }
+
+    pub fn path(&self, chapter: &Path, index: usize, variant: Option<&str>) -> PathBuf {
+        let name = match variant {
+            None => format!("{index}.out"),
+            Some(variant) => format!("{index}.{variant}.out"),
+        };
+        self.dir.join(chapter.with_extension("")).join(name)
+    }
+
+    /// Read a snapshot, `None` if it was never recorded.
+    pub fn read(
+        &self,
+        chapter: &Path,
+        index: usize,
+        variant: Option<&str>,
+    ) -> Result<Option<String>> {
+        let path = self.path(chapter, index, variant);
+
+        match fs::read_to_string(&path) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Fail to read snapshot {}", path.display())),
+        }
+    }
+
+    pub fn write(
+        &self,
+        chapter: &Path,
+        index: usize,
+        variant: Option<&str>,
+        content: &str,
+    ) -> Result<()> {
+        let path = self.path(chapter, index, variant);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Fail to create directory {}", parent.display()))?;
+        }
+        fs::write(&path, content)
+            .with_context(|| format!("Fail to write snapshot {}", path.display()))
+    }
+
+    /// Remove the snapshots which aren't in `kept`, like those of directives which no longer exist.
+    pub fn retain(&self, kept: &HashSet<PathBuf>) -> Result<()> {
+        Self::retain_in(&self.dir, kept)
+    }
+
+    fn retain_in(dir: &Path, kept: &HashSet<PathBuf>) -> Result<()> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e).with_context(|| format!("Fail to read {}", dir.display())),
+        };
+
+        for entry in entries {
+            let path = entry
+                .with_context(|| format!("Fail to read {}", dir.display()))?
+                .path();
+            if path.is_dir() {
+                Self::retain_in(&path, kept)?;
+                // Directories of removed chapters are removed along with their snapshots
+                let _ = fs::remove_dir(&path);
+            } else if !kept.contains(&path) {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Fail to remove snapshot {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Unified diff between a snapshot and the actual output of a directive.
+pub fn unified_diff(snapshot: &str, actual: &str) -> String {
+    TextDiff::from_lines(snapshot, actual)
+        .unified_diff()
+        .header("snapshot", "actual")
+        .to_string()
+}
@@ -0,0 +1,166 @@
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::config::Escape;
+
+/// Alignment of a column of a markdown table.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    pub fn parse(align: &str) -> Result<Align> {
+        match align {
+            "none" | "" => Ok(Align::None),
+            "left" | "l" => Ok(Align::Left),
+            "center" | "c" => Ok(Align::Center),
+            "right" | "r" => Ok(Align::Right),
+            _ => bail!("Unknown alignment '{align}', expected left, center, right or none"),
+        }
+    }
+
+    fn separator(self) -> &'static str {
+        match self {
+            Align::None => "---",
+            Align::Left => ":---",
+            Align::Center => ":---:",
+            Align::Right => "---:",
+        }
+    }
+}
+
+/// Render the output of a command as a markdown table.
+///
+/// The output is either a JSON array of objects, whose keys are the columns,
+/// or CSV whose first record holds the names of the columns.
+/// `columns` selects and orders the columns, and `align` gives the alignment of each column;
+/// a single alignment applies to every column.
+/// The cells are escaped according to `escape`, without wrapping them in `<pre>` elements.
+pub fn to_table(
+    output: &str,
+    columns: Option<&[String]>,
+    align: &[Align],
+    escape: Escape,
+) -> Result<String> {
+    let (header, rows) = if output.trim_start().starts_with('[') {
+        parse_json(output)?
+    } else {
+        parse_csv(output)?
+    };
+
+    let indexes = match columns {
+        None => (0..header.len()).collect::<Vec<_>>(),
+        Some(columns) => columns
+            .iter()
+            .map(|c| {
+                header
+                    .iter()
+                    .position(|h| h == c)
+                    .ok_or_else(|| anyhow!("Unknown column '{c}'"))
+            })
+            .collect::<Result<_>>()?,
+    };
+    let column_align = |i: usize| match align {
+        [single] => *single,
+        align => align.get(i).copied().unwrap_or_default(),
+    };
+
+    let mut table = String::new();
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+
+    table.push_str(&line(
+        indexes
+            .iter()
+            .map(|&i| escape_cell(&header[i], escape))
+            .collect(),
+    ));
+    table.push_str(&line(
+        (0..indexes.len())
+            .map(|i| column_align(i).separator().to_string())
+            .collect(),
+    ));
+    for row in rows {
+        table.push_str(&line(
+            indexes
+                .iter()
+                .map(|&i| escape_cell(row.get(i).map(String::as_str).unwrap_or_default(), escape))
+                .collect(),
+        ));
+    }
+    Ok(table)
+}
+
+fn parse_json(output: &str) -> Result<(Vec<String>, Vec<Vec<String>>)> {
+    let value: Value = serde_json::from_str(output).context("Unable to parse output as JSON")?;
+    let objects = value
+        .as_array()
+        .ok_or_else(|| anyhow!("Expected a JSON array of objects"))?
+        .iter()
+        .map(|v| {
+            v.as_object()
+                .ok_or_else(|| anyhow!("Expected a JSON array of objects"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Columns in order of first appearance
+    let mut header: Vec<String> = vec![];
+    for key in objects.iter().flat_map(|o| o.keys()) {
+        if !header.contains(key) {
+            header.push(key.clone());
+        }
+    }
+
+    let rows = objects
+        .iter()
+        .map(|o| {
+            header
+                .iter()
+                .map(|key| match o.get(key) {
+                    None | Some(Value::Null) => String::new(),
+                    Some(Value::String(s)) => s.clone(),
+                    Some(value) => value.to_string(),
+                })
+                .collect()
+        })
+        .collect();
+
+    Ok((header, rows))
+}
+
+fn parse_csv(output: &str) -> Result<(Vec<String>, Vec<Vec<String>>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(output.as_bytes());
+
+    let header = reader
+        .headers()
+        .context("Unable to parse output as CSV")?
+        .iter()
+        .map(String::from)
+        .collect();
+    let rows = reader
+        .records()
+        .map(|r| Ok(r?.iter().map(String::from).collect()))
+        .collect::<Result<_>>()
+        .context("Unable to parse output as CSV")?;
+
+    Ok((header, rows))
+}
+
+fn escape_cell(cell: &str, escape: Escape) -> String {
+    let cell = escape.text(cell.trim());
+    // Markdown escaping already takes care of pipes
+    let cell = match escape {
+        Escape::Markdown => cell,
+        _ => cell.replace('|', "\\|"),
+    };
+    cell.replace("\r\n", "<br>").replace(['\r', '\n'], "<br>")
+}
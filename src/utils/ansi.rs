@@ -0,0 +1,158 @@
+use std::fmt::Write;
+
+use lazy_static::lazy_static;
+use regex::Captures;
+use regex::Regex;
+
+lazy_static! {
+    // CSI sequences (colors, cursor movements, ...) and OSC sequences (titles, hyperlinks, ...)
+    static ref ANSI_REG: Regex = Regex::new(r"\x1b\[([0-9;?]*)([@-~])|\x1b\][^\x07\x1b]*(\x07|\x1b\\)")
+        .expect("Failed to init regex for finding ANSI escape codes");
+}
+
+const PALETTE: [&str; 16] = [
+    "#000000", "#cd0000", "#00cd00", "#cdcd00", "#0000ee", "#cd00cd", "#00cdcd", "#e5e5e5",
+    "#7f7f7f", "#ff0000", "#00ff00", "#ffff00", "#5c5cff", "#ff00ff", "#00ffff", "#ffffff",
+];
+
+/// Remove every ANSI escape code.
+pub fn strip(str: &str) -> String {
+    ANSI_REG.replace_all(str, "").to_string()
+}
+
+/// Apply `func` to the text between ANSI escape codes, leaving the codes untouched.
+pub fn map_text<F: Fn(&str) -> String>(str: &str, func: F) -> String {
+    let mut result = String::with_capacity(str.len());
+    let mut last = 0;
+
+    for code in ANSI_REG.find_iter(str) {
+        result.push_str(&func(&str[last..code.start()]));
+        result.push_str(code.as_str());
+        last = code.end();
+    }
+    result.push_str(&func(&str[last..]));
+    result
+}
+
+/// Turn ANSI colors and text attributes (SGR codes) into HTML `<span>` elements,
+/// other escape codes are removed.
+pub fn to_html(str: &str) -> String {
+    let mut style = Style::default();
+    let mut open = false;
+
+    let mut result = ANSI_REG
+        .replace_all(str, |caps: &Captures| {
+            if caps.get(2).map(|m| m.as_str()) != Some("m") {
+                return String::new();
+            }
+
+            style.apply(&caps[1]);
+
+            let mut span = String::new();
+            if open {
+                span.push_str("</span>");
+            }
+            let css = style.css();
+            open = !css.is_empty();
+            if open {
+                write!(span, "<span style=\"{css}\">").unwrap();
+            }
+            span
+        })
+        .to_string();
+
+    if open {
+        result.push_str("</span>");
+    }
+    result
+}
+
+#[derive(Default)]
+struct Style {
+    foreground: Option<String>,
+    background: Option<String>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl Style {
+    fn apply(&mut self, params: &str) {
+        let mut codes = params.split(';').map(|c| c.parse::<u8>().unwrap_or(0));
+
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                22 => self.bold = false,
+                23 => self.italic = false,
+                24 => self.underline = false,
+                30..=37 => self.foreground = Some(PALETTE[(code - 30) as usize].to_string()),
+                38 => self.foreground = Self::extended_color(&mut codes),
+                39 => self.foreground = None,
+                40..=47 => self.background = Some(PALETTE[(code - 40) as usize].to_string()),
+                48 => self.background = Self::extended_color(&mut codes),
+                49 => self.background = None,
+                90..=97 => self.foreground = Some(PALETTE[(code - 82) as usize].to_string()),
+                100..=107 => self.background = Some(PALETTE[(code - 92) as usize].to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    // 256 colors (`5;n`) and true colors (`2;r;g;b`)
+    fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Option<String> {
+        match codes.next() {
+            Some(5) => {
+                let n = codes.next()?;
+                match n {
+                    0..=15 => Some(PALETTE[n as usize].to_string()),
+                    16..=231 => {
+                        let level = |c: u8| if c == 0 { 0 } else { 55 + c * 40 };
+                        let n = n - 16;
+                        Some(format!(
+                            "#{:02x}{:02x}{:02x}",
+                            level(n / 36),
+                            level((n / 6) % 6),
+                            level(n % 6)
+                        ))
+                    }
+                    232..=255 => {
+                        let gray = 8 + (n - 232) * 10;
+                        Some(format!("#{gray:02x}{gray:02x}{gray:02x}"))
+                    }
+                }
+            }
+            Some(2) => Some(format!(
+                "#{:02x}{:02x}{:02x}",
+                codes.next()?,
+                codes.next()?,
+                codes.next()?
+            )),
+            _ => None,
+        }
+    }
+
+    fn css(&self) -> String {
+        let mut css = vec![];
+
+        if let Some(color) = &self.foreground {
+            css.push(format!("color:{color}"));
+        }
+        if let Some(color) = &self.background {
+            css.push(format!("background-color:{color}"));
+        }
+        if self.bold {
+            css.push("font-weight:bold".to_string());
+        }
+        if self.italic {
+            css.push("font-style:italic".to_string());
+        }
+        if self.underline {
+            css.push("text-decoration:underline".to_string());
+        }
+        css.join(";")
+    }
+}
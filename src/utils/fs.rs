@@ -0,0 +1,62 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use anyhow::Context;
+use anyhow::Result;
+
+/// Copy the content of the directory `from` into `to`, recursively, except the entries for which `skip` is true.
+/// Symbolic links are copied as links, without following them.
+pub fn copy_dir(from: &Path, to: &Path, skip: &impl Fn(&Path) -> bool) -> Result<()> {
+    fs::create_dir_all(to)
+        .with_context(|| format!("Unable to create directory {}", to.display()))?;
+
+    let entries = fs::read_dir(from)
+        .with_context(|| format!("Unable to read directory {}", from.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let (source, destination) = (entry.path(), to.join(entry.file_name()));
+        if skip(&source) {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            copy_symlink(&source, &destination)
+                .with_context(|| format!("Unable to copy link {}", source.display()))?;
+        } else if file_type.is_dir() {
+            copy_dir(&source, &destination, skip)?;
+        } else {
+            fs::copy(&source, &destination)
+                .with_context(|| format!("Unable to copy {}", source.display()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_family = "unix")]
+fn copy_symlink(source: &Path, destination: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(source)?, destination)
+}
+
+// Windows has distinct links to files and to directories
+#[cfg(target_family = "windows")]
+fn copy_symlink(source: &Path, destination: &Path) -> io::Result<()> {
+    let target = fs::read_link(source)?;
+    match fs::metadata(source).is_ok_and(|metadata| metadata.is_dir()) {
+        true => std::os::windows::fs::symlink_dir(target, destination),
+        false => std::os::windows::fs::symlink_file(target, destination),
+    }
+}
+
+/// Write a file, unless it already has this content.
+/// Rewriting an identical file would trigger `mdbook serve` again.
+pub fn write_if_changed(path: &Path, content: &[u8]) -> Result<()> {
+    if fs::read(path).ok().as_deref() == Some(content) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Unable to create directory {}", parent.display()))?;
+    }
+    fs::write(path, content).with_context(|| format!("Unable to write {}", path.display()))
+}
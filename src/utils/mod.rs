@@ -1,3 +1,7 @@
+pub mod ansi;
+pub mod fs;
 pub mod map_chapter;
+pub mod text;
+pub mod words;
 
 pub use map_chapter::map_chapter;
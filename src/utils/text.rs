@@ -0,0 +1,46 @@
+// ASCII punctuation that can start or end markdown syntax
+const MARKDOWN_SPECIAL: &str = "\\`*_{}[]<>()#+-=.!|~&";
+
+/// Remove the indentation shared by every non-blank line.
+pub fn dedent(str: &str) -> String {
+    let indent = str
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    str.split_inclusive('\n')
+        .map(|l| l.get(indent..).unwrap_or(l.trim_start_matches([' ', '\t'])))
+        .collect()
+}
+
+/// Escape the characters having a meaning in HTML.
+pub fn escape_html(str: &str) -> String {
+    let mut escaped = String::with_capacity(str.len());
+
+    for c in str.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escape the characters having a meaning in markdown, HTML tags included, with backslashes.
+pub fn escape_markdown(str: &str) -> String {
+    let mut escaped = String::with_capacity(str.len());
+
+    for c in str.chars() {
+        if MARKDOWN_SPECIAL.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,103 @@
+use anyhow::bail;
+use anyhow::Result;
+
+/// Split the first word of `str`, following shell rules for quotes and backslashes,
+/// and return it along with the rest of the string.
+/// Returns `None` if `str` only contains whitespaces.
+pub fn next_word(str: &str) -> Result<Option<(String, &str)>> {
+    let str = str.trim_start();
+    if str.is_empty() {
+        return Ok(None);
+    }
+
+    let mut word = String::new();
+    let mut chars = str.char_indices();
+    let mut quote = None;
+
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => return Ok(Some((word, &str[i..]))),
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '\\') | (Some('"'), '\\') => match chars.next() {
+                // inside double quotes, a backslash only escapes some characters
+                Some((_, e)) if quote.is_none() || matches!(e, '"' | '\\' | '$' | '`') => {
+                    word.push(e)
+                }
+                Some((_, e)) => {
+                    word.push('\\');
+                    word.push(e);
+                }
+                None => word.push('\\'),
+            },
+            (Some(q), c) if c == q => quote = None,
+            (_, c) => word.push(c),
+        }
+    }
+
+    if let Some(q) = quote {
+        bail!("Unterminated quote {q}");
+    }
+    Ok(Some((word, "")))
+}
+
+/// Split `str` into words, following the same rules as [`next_word`].
+pub fn split(mut str: &str) -> Result<Vec<String>> {
+    let mut words = vec![];
+    while let Some((word, rest)) = next_word(str)? {
+        words.push(word);
+        str = rest;
+    }
+    Ok(words)
+}
+
+/// Quote a word for the shell running the commands, if it contains special characters.
+pub fn quote(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if !word.is_empty() && word.chars().all(safe) {
+        return word.to_string();
+    }
+
+    if cfg!(target_family = "windows") {
+        format!("\"{}\"", word.replace('"', "\"\""))
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Quote a word inserted in the shell command `before`, as an argument of its own or inside the quotes left open by `before`.
+pub fn quote_after(before: &str, word: &str) -> String {
+    match open_quote(before) {
+        None => quote(word),
+        Some('\'') if cfg!(target_family = "unix") => word.replace('\'', "'\\''"),
+        Some('"') if cfg!(target_family = "windows") => word.replace('"', "\"\""),
+        Some('"') => {
+            let mut escaped = String::with_capacity(word.len());
+            for c in word.chars() {
+                if matches!(c, '"' | '\\' | '$' | '`') {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+            escaped
+        }
+        // cmd doesn't have single quotes
+        Some(_) => quote(word),
+    }
+}
+
+// Quote which is still open at the end of `str`, following the same rules as `next_word`.
+fn open_quote(str: &str) -> Option<char> {
+    let mut chars = str.chars();
+    let mut quote = None;
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '\\') | (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    quote
+}
@@ -0,0 +1,42 @@
+use anyhow::anyhow;
+use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::Captures;
+use regex::Regex;
+
+use crate::utils::words::quote_after;
+
+lazy_static! {
+    static ref VAR_REG: Regex = Regex::new(r"(\\)?\{\{\s*([A-Za-z_][A-Za-z0-9_-]*)\s*\}\}")
+        .expect("Failed to init regex for finding variables");
+}
+
+/// Replace every `{{name}}` of a directive by the value of the variable, quoted for the shell.
+/// Values inside single or double quotes are escaped for these quotes rather than quoted again.
+/// `\{{name}}` is replaced by `{{name}}`, without interpolating it.
+pub fn interpolate<F>(directive: &str, lookup: F) -> Result<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut err = None;
+
+    let result = VAR_REG
+        .replace_all(directive, |caps: &Captures| {
+            if caps.get(1).is_some() {
+                return caps[0][1..].to_string();
+            }
+            match lookup(&caps[2]) {
+                Some(value) => quote_after(&directive[..caps.get(0).unwrap().start()], &value),
+                None => {
+                    err.get_or_insert_with(|| anyhow!("Unknown variable '{}'", &caps[2]));
+                    String::new()
+                }
+            }
+        })
+        .to_string();
+
+    match err {
+        None => Ok(result),
+        Some(err) => Err(err),
+    }
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use mdbook_cmdrun::cmdrun::RunOptions;
+use mdbook_cmdrun::CmdRun;
+
+fn options() -> RunOptions {
+    let config = common::config(
+        r#"
+        [preprocessor.cmdrun.vars]
+        greeting = "hello"
+
+        [preprocessor.cmdrun.aliases]
+        greet = "echo {{greeting}}"
+        list = "ls {{src_dir}}"
+        list_root = "ls {{root}}/src"
+        fail = "echo failed; exit 3"
+        "#,
+    )
+    .unwrap();
+    common::chapter_options(&config)
+}
+
+fn run(command: &str) -> String {
+    common::run(command, &options())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn expanded() {
+    assert_eq!(run("@greet"), "hello");
+    assert_eq!(run("@greet world again"), "hello world again");
+    assert!(run("@list").lines().any(|file| file == "SUMMARY.md"));
+    assert!(run("@list_root").lines().any(|file| file == "SUMMARY.md"));
+    assert_eq!(run("echo @greet"), "@greet");
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn with_flags() {
+    assert_eq!(
+        run("--strict @fail"),
+        "**cmdrun error**: 'echo failed; exit 3' returned exit code 3 instead of 0.\nfailed\n\n"
+    );
+    let options = RunOptions {
+        renderer: Some("html".to_string()),
+        ..options()
+    };
+    let output = CmdRun::run_directive(
+        "--renderer nope --else '@greet fallback' echo selected".to_string(),
+        true,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(output.text, "hello fallback");
+}
+
+#[test]
+fn unknown() {
+    assert_eq!(
+        run("@nope a b"),
+        "**cmdrun error**: Unknown alias '@nope' in 'cmdrun @nope a b'"
+    );
+}
@@ -0,0 +1,118 @@
+use std::str::FromStr;
+
+use mdbook_cmdrun::cmdrun::RunOptions;
+use mdbook_cmdrun::config::Ansi;
+use mdbook_cmdrun::config::Config;
+use mdbook_cmdrun::config::Escape;
+use mdbook_cmdrun::CmdRun;
+
+macro_rules! add_test {
+    ($name:ident, $cmd:literal, $ansi:expr, $output:expr $(,)?) => {
+        #[test]
+        fn $name() {
+            let options = RunOptions {
+                working_dir: ".".to_string(),
+                ansi: $ansi,
+                ..Default::default()
+            };
+            let actual_output = CmdRun::run_directive($cmd.to_string(), true, &options)
+                .unwrap()
+                .text;
+
+            assert_eq!(actual_output, $output.to_string());
+        }
+    };
+}
+
+add_test!(
+    keep,
+    "printf '\\033[31mred\\033[0m'",
+    Ansi::Keep,
+    "\x1b[31mred\x1b[0m"
+);
+add_test!(
+    strip,
+    "printf '\\033[1;31mred\\033[0m plain \\033[2K'",
+    Ansi::Strip,
+    "red plain"
+);
+add_test!(plain_html, "echo plain", Ansi::Html, "plain");
+add_test!(
+    html,
+    "printf '\\033[31mred\\033[0m plain'",
+    Ansi::Html,
+    "<span style=\"color:#cd0000\">red</span> plain"
+);
+add_test!(
+    html_combined,
+    "printf '\\033[1;4;92mgreen\\033[22m thin\\033[0m'",
+    Ansi::Html,
+    "<span style=\"color:#00ff00;font-weight:bold;text-decoration:underline\">green</span>\
+     <span style=\"color:#00ff00;text-decoration:underline\"> thin</span>"
+);
+add_test!(
+    html_extended_colors,
+    "printf '\\033[38;5;196;48;2;0;128;255mcolors'",
+    Ansi::Html,
+    "<span style=\"color:#ff0000;background-color:#0080ff\">colors</span>"
+);
+
+#[test]
+fn html_escaped() {
+    let options = RunOptions {
+        working_dir: ".".to_string(),
+        ansi: Ansi::Html,
+        escape: Escape::Markdown,
+        ..Default::default()
+    };
+    let actual_output = CmdRun::run_directive(
+        "printf '\\033[31m<error>\\033[0m [x]'".to_string(),
+        true,
+        &options,
+    )
+    .unwrap()
+    .text;
+
+    assert_eq!(
+        actual_output,
+        "<span style=\"color:#cd0000\">\\<error\\></span> \\[x\\]"
+    );
+}
+
+#[test]
+fn pre_escaped() {
+    let options = RunOptions {
+        working_dir: ".".to_string(),
+        ansi: Ansi::Html,
+        escape: Escape::Pre,
+        ..Default::default()
+    };
+    let actual_output = CmdRun::run_directive(
+        "printf '\\033[1m<b>\\033[0m\\n'".to_string(),
+        false,
+        &options,
+    )
+    .unwrap()
+    .text;
+
+    assert_eq!(
+        actual_output,
+        "<pre><span style=\"font-weight:bold\">&lt;b&gt;</span></pre>\n"
+    );
+}
+
+#[test]
+fn default_per_renderer() {
+    let book_config = mdbook::Config::from_str(
+        r#"
+        [preprocessor.cmdrun.renderer.html]
+        ansi = "html"
+        "#,
+    )
+    .unwrap();
+    let config = Config::from_book_config(&book_config).unwrap();
+
+    assert_eq!(config.ansi("html"), Ansi::Html);
+    assert_eq!(config.ansi("markdown"), Ansi::Keep);
+    assert_eq!(Config::default().ansi("html"), Ansi::Keep);
+}
@@ -1,6 +1,8 @@
 use std::fs;
 use std::process::Command;
 
+use mdbook_cmdrun::check::{check, CheckOptions, SnapshotMode};
+
 #[test]
 fn build_test_book() {
     let output = Command::new("mdbook")
@@ -28,3 +30,129 @@ fn build_test_book_with_dynamic_src() {
 
     assert_eq!(output.status.code().unwrap(), 0);
 }
+
+#[test]
+fn build_test_book_frozen() {
+    let book_dir = fs::canonicalize("./tests/book_frozen/").unwrap();
+    let output = Command::new("mdbook")
+        .arg("build")
+        .current_dir(&book_dir)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code().unwrap(), 0);
+
+    let html = fs::read_to_string(book_dir.join("book/frozen.html")).unwrap();
+    assert!(html.contains("<td>frozen</td><td>snapshot</td>"));
+}
+
+#[test]
+fn build_test_book_frozen_missing_snapshot() {
+    let output = Command::new("mdbook")
+        .arg("build")
+        .current_dir(fs::canonicalize("./tests/book_frozen_missing/").unwrap())
+        .output()
+        .unwrap();
+
+    assert_ne!(output.status.code().unwrap(), 0);
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Missing snapshot"));
+}
+
+#[test]
+// The book uses printf, which cmd doesn't have
+#[cfg(target_family = "unix")]
+fn build_test_book_with_renderers() {
+    let book_dir = fs::canonicalize("./tests/book_renderers/").unwrap();
+    let output = Command::new("mdbook")
+        .arg("build")
+        .current_dir(&book_dir)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code().unwrap(), 0);
+
+    let markdown = fs::read_to_string(book_dir.join("book/markdown/colors.md")).unwrap();
+    assert_eq!(markdown, "# Colors\n\nred\nmarkdown\n");
+
+    let html = fs::read_to_string(book_dir.join("book/html/colors.html")).unwrap();
+    assert!(html.contains("<span style=\"color:#cd0000\">red</span>"));
+    assert!(html.contains("html only"));
+    assert!(html.contains("not markdown"));
+}
+
+#[test]
+fn build_test_book_with_artifacts() {
+    let book_dir = fs::canonicalize("./tests/book_artifacts/").unwrap();
+    let output = Command::new("mdbook")
+        .arg("build")
+        .current_dir(&book_dir)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code().unwrap(), 0);
+
+    let html = fs::read_to_string(book_dir.join("book/diagrams.html")).unwrap();
+    assert!(html.contains("<img src=\"cmdrun-artifacts/diagrams/square.svg\" alt=\"A square\""));
+    assert!(html.contains("<svg xmlns=\"http://www.w3.org/2000/svg\">\n<circle r=\"5\"/></svg>"));
+    assert!(html.contains("Unable to read artifact"));
+    assert!(book_dir
+        .join("book/cmdrun-artifacts/diagrams/square.svg")
+        .exists());
+
+    let html = fs::read_to_string(book_dir.join("book/nested/plot.html")).unwrap();
+    assert!(html.contains("<img src=\"data:image/svg+xml;base64,PHN2Zy8+\" alt=\"plot.svg\""));
+    assert!(html.contains("<img src=\"../cmdrun-artifacts/nested/plot/plot.svg\" alt=\"plot.svg\""));
+    assert!(book_dir
+        .join("book/cmdrun-artifacts/nested/plot/plot.svg")
+        .exists());
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn build_test_book_frozen_with_renderers() {
+    let book_dir = tempfile::tempdir().unwrap();
+    let book_dir = book_dir.path();
+    let book_toml = |frozen: bool| {
+        format!(
+            "[book]\ntitle = \"Frozen Renderers\"\n\n[preprocessor.cmdrun]\ncommand = \"{}\"\nfrozen = {frozen}\n\n[output.html]\n\n[output.markdown]\n",
+            env!("CARGO_BIN_EXE_mdbook-cmdrun")
+        )
+    };
+    fs::create_dir(book_dir.join("src")).unwrap();
+    fs::write(
+        book_dir.join("src/SUMMARY.md"),
+        "- [Chapter](./chapter.md)\n",
+    )
+    .unwrap();
+    fs::write(book_dir.join("src/old.txt"), "a\nb\n").unwrap();
+    fs::write(book_dir.join("src/new.txt"), "a\nc\n").unwrap();
+    fs::write(
+        book_dir.join("src/chapter.md"),
+        "<!-- cmdrun --renderer html --else \"echo plain\" echo '<b>html</b>' -->\n<!-- cmdrun --diff old.txt new.txt --side-by-side -->\n",
+    )
+    .unwrap();
+    fs::write(book_dir.join("book.toml"), book_toml(false)).unwrap();
+
+    let update = CheckOptions {
+        snapshots: SnapshotMode::Update,
+        ..Default::default()
+    };
+    assert!(check(book_dir, &update).unwrap().is_success());
+    fs::write(book_dir.join("book.toml"), book_toml(true)).unwrap();
+    let output = Command::new("mdbook")
+        .arg("build")
+        .current_dir(book_dir)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code().unwrap(), 0);
+
+    let markdown = fs::read_to_string(book_dir.join("book/markdown/chapter.md")).unwrap();
+    assert!(markdown.starts_with("plain\n"), "{markdown}");
+    assert!(!markdown.contains('<'), "{markdown}");
+    assert!(markdown.contains("-b\n+c\n"), "{markdown}");
+    let html = fs::read_to_string(book_dir.join("book/html/chapter.html")).unwrap();
+    assert!(html.contains("<b>html</b>"));
+    assert!(html.contains("<table"));
+}
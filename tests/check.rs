@@ -0,0 +1,174 @@
+use std::fs;
+use std::path::Path;
+
+use mdbook_cmdrun::check::{check, CheckOptions, SnapshotMode};
+
+#[test]
+// The test book pipes unix tools, which cmd doesn't have
+#[cfg(target_family = "unix")]
+fn check_test_book() {
+    let options = CheckOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let report = check(Path::new("./tests/book"), &options).unwrap();
+
+    assert!(report.is_success(), "{report}");
+    assert_eq!(report.chapters.len(), 2);
+    assert_eq!(report.directive_count(), 5);
+}
+
+#[test]
+fn check_failing_book() {
+    let report = check(Path::new("./tests/book_check"), &CheckOptions::default()).unwrap();
+
+    assert!(!report.is_success());
+    assert_eq!(report.directive_count(), 4);
+    assert_eq!(report.failure_count(), 1);
+
+    let failing = &report.chapters[1];
+    assert_eq!(failing.path, "failing.md");
+    assert_eq!(
+        failing.directives[0].error.as_deref(),
+        Some("'exit 3' returned exit code 3 instead of 0")
+    );
+    assert_eq!(failing.directives[1].error, None);
+}
+
+#[test]
+fn check_failing_book_strict() {
+    let options = CheckOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let report = check(Path::new("./tests/book_check"), &options).unwrap();
+
+    assert_eq!(report.failure_count(), 2);
+    assert_eq!(
+        report.chapters[1].directives[1].error.as_deref(),
+        Some("'exit 1' returned exit code 1 instead of 0")
+    );
+}
+
+#[test]
+// The snapshots are the outputs of unix tools
+#[cfg(target_family = "unix")]
+fn check_test_book_snapshots() {
+    let options = CheckOptions {
+        snapshots: SnapshotMode::Verify,
+        ..Default::default()
+    };
+    let report = check(Path::new("./tests/book"), &options).unwrap();
+
+    assert!(report.is_success(), "{report}");
+}
+
+#[test]
+// The snapshots and the diff have the line endings of sh
+#[cfg(target_family = "unix")]
+fn check_snapshot_mismatch() {
+    let options = CheckOptions {
+        snapshots: SnapshotMode::Verify,
+        ..Default::default()
+    };
+    let report = check(Path::new("./tests/book_check"), &options).unwrap();
+    let passing = &report.chapters[0];
+    let failing = &report.chapters[1];
+
+    assert_eq!(report.failure_count(), 3);
+    assert_eq!(
+        passing.directives[0].diff.as_deref(),
+        Some("--- snapshot\n+++ actual\n@@ -1 +1 @@\n-goodbye\n+hello\n")
+    );
+    assert_eq!(passing.directives[1].error, None);
+    // A failing command is reported as such, whatever its snapshot
+    assert_eq!(
+        failing.directives[0].error.as_deref(),
+        Some("'exit 3' returned exit code 3 instead of 0")
+    );
+    assert!(failing.directives[1]
+        .error
+        .as_deref()
+        .unwrap()
+        .starts_with("missing snapshot"));
+}
+
+#[test]
+// cmd ends lines with \r\n
+#[cfg(target_family = "unix")]
+fn check_update_snapshots() {
+    let book_dir = tempfile::tempdir().unwrap();
+    let book_dir = book_dir.path();
+    fs::create_dir_all(book_dir.join("src")).unwrap();
+    for file in ["book.toml", "src/SUMMARY.md", "src/passing.md"] {
+        fs::copy(
+            Path::new("./tests/book_check").join(file),
+            book_dir.join(file),
+        )
+        .unwrap();
+    }
+
+    let update = CheckOptions {
+        snapshots: SnapshotMode::Update,
+        ..Default::default()
+    };
+    let verify = CheckOptions {
+        snapshots: SnapshotMode::Verify,
+        ..Default::default()
+    };
+
+    assert!(!check(book_dir, &verify).unwrap().is_success());
+    assert!(check(book_dir, &update).unwrap().is_success());
+    assert!(check(book_dir, &verify).unwrap().is_success());
+    assert_eq!(
+        fs::read_to_string(book_dir.join(".cmdrun-snapshots/passing/1.out")).unwrap(),
+        "hello\n"
+    );
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn check_update_snapshots_failing() {
+    let book_dir = tempfile::tempdir().unwrap();
+    let book_dir = book_dir.path();
+    let snapshots = book_dir.join(".cmdrun-snapshots");
+    fs::create_dir_all(book_dir.join("src")).unwrap();
+    for file in [
+        "book.toml",
+        "src/SUMMARY.md",
+        "src/passing.md",
+        "src/failing.md",
+    ] {
+        fs::copy(
+            Path::new("./tests/book_check").join(file),
+            book_dir.join(file),
+        )
+        .unwrap();
+    }
+    fs::create_dir_all(snapshots.join("failing")).unwrap();
+    fs::create_dir_all(snapshots.join("removed")).unwrap();
+    fs::write(snapshots.join("failing/1.out"), "recorded\n").unwrap();
+    fs::write(snapshots.join("failing/3.out"), "stale\n").unwrap();
+    fs::write(snapshots.join("removed/1.out"), "stale\n").unwrap();
+
+    let update = CheckOptions {
+        snapshots: SnapshotMode::Update,
+        ..Default::default()
+    };
+    assert_eq!(check(book_dir, &update).unwrap().failure_count(), 1);
+
+    assert_eq!(
+        fs::read_to_string(snapshots.join("failing/1.out")).unwrap(),
+        "recorded\n"
+    );
+    assert_eq!(
+        fs::read_to_string(snapshots.join("failing/2.out")).unwrap(),
+        ""
+    );
+    assert!(!snapshots.join("failing/3.out").exists());
+    assert!(!snapshots.join("removed").exists());
+    assert_eq!(
+        fs::read_to_string(snapshots.join("passing/1.out")).unwrap(),
+        "hello\n"
+    );
+}
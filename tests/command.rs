@@ -191,3 +191,209 @@ add_test!(missing_arg_no_code, "--expect-return-code echo hello world",
 add_test!(bad_short_form_exit_code, "-NaN echo hello world",
           "**cmdrun error**: Unable to interpret short-form exit code -NaN as a number in 'cmdrun -NaN echo hello world'",
           false);
+add_test!(
+    flags_keep_command_spacing,
+    "-0 printf '%s|%s'   a   b",
+    "a|b",
+    true
+);
+add_test!(end_of_flags, "--strict -- echo --strict", "--strict", true);
+add_test!(
+    renderer_without_context,
+    "--renderer epub echo epub",
+    "epub",
+    true
+);
+add_test!(
+    renderer_else_without_context,
+    "--renderer epub --else \"echo other\" echo epub",
+    "epub",
+    true
+);
+add_test!(
+    renderer_no_value,
+    "--renderer",
+    "**cmdrun error**: No value after '--renderer' in 'cmdrun --renderer'",
+    true
+);
+add_test!(
+    else_without_renderer,
+    "--else 'echo other' echo main",
+    "**cmdrun error**: '--else' can only be used along with '--renderer' in 'cmdrun --else 'echo other' echo main'",
+    true
+);
+add_test!(
+    platform_variant,
+    "--unix 'echo unix' --windows 'echo windows' echo other",
+    if cfg!(target_family = "windows") {
+        "windows"
+    } else {
+        "unix"
+    },
+    true
+);
+add_test!(
+    platform_variant_with_include,
+    "--include file.txt --unix 'echo unix'",
+    "**cmdrun error**: '--include' can't be used along with a command in 'cmdrun --include file.txt --unix 'echo unix''",
+    true
+);
+add_test!(
+    unterminated_quote,
+    "--renderer 'html echo main",
+    "**cmdrun error**: Unterminated quote ' in 'cmdrun --renderer 'html echo main'",
+    true
+);
+
+cfg_if! {
+    if #[cfg(target_family = "unix")] {
+        add_test!(encoding_invalid_utf8, "printf '\\351t\\351'", "\u{FFFD}t\u{FFFD}", true);
+        add_test!(
+            encoding_invalid_utf8_strict,
+            "--strict-encoding printf '\\351t\\351'",
+            "**cmdrun error**: Output is not valid UTF-8, use '--encoding' to set its encoding: invalid utf-8 sequence of 1 bytes from index 0 in 'cmdrun --strict-encoding printf '\\351t\\351''",
+            true
+        );
+        add_test!(encoding_latin1, "--encoding latin1 printf '\\351t\\351'", "été", true);
+        add_test!(
+            encoding_utf16,
+            "--encoding utf-16 printf '\\377\\376h\\000\\351\\000'",
+            "hé",
+            true
+        );
+        add_test!(
+            encoding_utf16be,
+            "--encoding=utf-16be printf '\\000h\\000\\351'",
+            "hé",
+            true
+        );
+        add_test!(
+            encoding_unknown,
+            "--encoding ebcdic echo",
+            "**cmdrun error**: Unknown encoding 'ebcdic', expected utf-8, latin1 or utf-16 in 'cmdrun --encoding ebcdic echo'",
+            true
+        );
+        add_test!(
+            binary_error,
+            "--binary error printf '\\211PNG\\r\\n\\032\\n\\000'",
+            "**cmdrun error**: Output looks like binary data (9 bytes), use '--binary data-uri' to insert it as a data URI in 'cmdrun --binary error printf '\\211PNG\\r\\n\\032\\n\\000''",
+            true
+        );
+        add_test!(
+            binary_strict_encoding,
+            "--strict-encoding printf 'GIF89a\\000'",
+            "**cmdrun error**: Output looks like binary data (7 bytes), use '--binary data-uri' to insert it as a data URI in 'cmdrun --strict-encoding printf 'GIF89a\\000''",
+            true
+        );
+        add_test!(
+            binary_data_uri,
+            "--binary data-uri printf '\\211PNG\\r\\n\\032\\n\\000'",
+            "data:image/png;base64,iVBORw0KGgoA",
+            true
+        );
+        add_test!(
+            binary_data_uri_svg,
+            "--binary data-uri echo '<svg/>'",
+            "data:image/svg+xml;base64,PHN2Zy8+Cg==\n",
+            false
+        );
+    }
+}
+
+add_test!(
+    expect_contains,
+    "--expect-contains 'lo wo' echo hello world",
+    "hello world",
+    true
+);
+add_test!(
+    expect_contains_failure,
+    "--expect-contains bye echo hello world",
+    &format!(
+        "**cmdrun error**: 'echo hello world' output doesn't contain 'bye'.{NL}hello world{NL}{NL}"
+    ),
+    true
+);
+add_test!(
+    expect_regex,
+    "--expect-regex '^hello \\w+$' echo hello world",
+    "hello world",
+    true
+);
+add_test!(
+    expect_regex_failure,
+    "--expect-regex '^world' echo hello world",
+    &format!("**cmdrun error**: 'echo hello world' output doesn't match '^world'.{NL}hello world{NL}{NL}"),
+    true
+);
+add_test!(
+    expect_lines_failure,
+    "--expect-lines 2 --expect-contains hello echo hello world",
+    &format!("**cmdrun error**: 'echo hello world' output has 1 lines instead of 2.{NL}hello world{NL}{NL}"),
+    true
+);
+add_test!(
+    expect_after_exit_code,
+    "--strict --expect-contains bye exit 1",
+    &format!("**cmdrun error**: 'exit 1' returned exit code 1 instead of 0.{NL}{NL}"),
+    true
+);
+add_test!(
+    expect_lines_invalid,
+    "--expect-lines many echo",
+    "**cmdrun error**: Unable to interpret many as a number of lines for '--expect-lines' in 'cmdrun --expect-lines many echo'",
+    true
+);
+
+add_test!(exit_code_set, "--expect-return-code 0,1 exit 1", "", true);
+add_test!(
+    exit_code_range,
+    "--expect-return-code=0..=2 exit 2",
+    "",
+    true
+);
+add_test!(
+    exit_code_range_failure,
+    "--expect-return-code 0..2 exit 2",
+    &format!("**cmdrun error**: 'exit 2' returned exit code 2 instead of 0..=1.{NL}{NL}"),
+    true
+);
+add_test!(
+    exit_code_invalid,
+    "--expect-return-code 2..=0 exit 2",
+    "**cmdrun error**: Invalid exit codes '2..=0', expected N, N,M or N..=M in 'cmdrun --expect-return-code 2..=0 exit 2'",
+    true
+);
+add_test!(any_exit, "--strict --any-exit exit 3", "", true);
+add_test!(expect_failure, "--expect-failure exit 3", "", true);
+add_test!(
+    expect_failure_success,
+    "--expect-failure echo hello",
+    &format!("**cmdrun error**: 'echo hello' returned exit code 0 instead of a non-zero code.{NL}hello{NL}{NL}"),
+    true
+);
+
+#[test]
+#[cfg(target_family = "unix")]
+fn terminated_by_signal() {
+    assert_eq!(
+        CmdRun::run_cmdrun("echo started; kill -9 $$".to_string(), ".", true).unwrap(),
+        "**cmdrun error**: 'echo started; kill -9 $$' was terminated by SIGKILL (9).\nstarted\n\n"
+    );
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn table_cell_line_breaks() {
+    let output = CmdRun::run_cmdrun(
+        r#"--format table printf '[{"a": "one\\ntwo", "b": "three\\r\\nfour\\rfive"}]'"#
+            .to_string(),
+        ".",
+        false,
+    )
+    .unwrap();
+    assert_eq!(
+        output,
+        "| a | b |\n| --- | --- |\n| one<br>two | three<br>four<br>five |\n"
+    );
+}
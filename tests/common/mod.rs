@@ -0,0 +1,50 @@
+// Helpers shared by the integration tests, each of which only uses some of them
+#![allow(dead_code)]
+
+use std::path::Path;
+use std::str::FromStr;
+
+use mdbook::book::Chapter;
+use mdbook_cmdrun::cmdrun::RunOptions;
+use mdbook_cmdrun::config::Config;
+use mdbook_cmdrun::CmdRun;
+
+/// Configuration of the preprocessor, from the content of a `book.toml`.
+pub fn config(toml: &str) -> anyhow::Result<Config> {
+    Config::from_book_config(&mdbook::Config::from_str(toml).unwrap())
+}
+
+/// Options shared by the chapters of the test book.
+pub fn book_options(config: &Config) -> RunOptions {
+    RunOptions::for_book(config, Path::new("tests/book"), Path::new("tests/book/src")).unwrap()
+}
+
+/// A chapter of the test book, in a subdirectory of its sources.
+pub fn chapter() -> Chapter {
+    Chapter::new("Echo again", String::new(), "nested/echo_again.md", vec![])
+}
+
+/// Options of [`chapter`].
+pub fn chapter_options(config: &Config) -> RunOptions {
+    book_options(config).for_chapter(config, &chapter())
+}
+
+/// Options running commands in `working_dir`, outside of any book.
+pub fn options_in(working_dir: &str) -> RunOptions {
+    RunOptions {
+        working_dir: working_dir.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Text inserted in place of an inline directive.
+pub fn run(command: &str, options: &RunOptions) -> String {
+    output(command, true, options)
+}
+
+/// Text inserted in place of a directive.
+pub fn output(command: &str, inline: bool, options: &RunOptions) -> String {
+    CmdRun::run_directive(command.to_string(), inline, options)
+        .unwrap()
+        .text
+}
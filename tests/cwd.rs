@@ -0,0 +1,78 @@
+mod common;
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use common::book_options;
+use common::chapter;
+use common::run;
+use mdbook_cmdrun::config::Config;
+use mdbook_cmdrun::config::WorkingDir;
+
+#[test]
+#[cfg(target_family = "unix")]
+fn prefixes() {
+    let options = common::chapter_options(&Config::default());
+
+    assert_eq!(run("ls echo.py", &options), "echo.py");
+    assert_eq!(run("--cwd @root ls book.toml", &options), "book.toml");
+    assert_eq!(run("--cwd @src ls SUMMARY.md", &options), "SUMMARY.md");
+    assert_eq!(run("--cwd @src/nested ls echo.py", &options), "echo.py");
+    assert_eq!(run("--cwd @repo ls Cargo.toml", &options), "Cargo.toml");
+    assert_eq!(run("--cwd .. ls SUMMARY.md", &options), "SUMMARY.md");
+}
+
+#[test]
+fn invalid() {
+    let options = common::chapter_options(&Config::default());
+
+    assert_eq!(
+        run("--cwd missing echo", &options),
+        format!(
+            "**cmdrun error**: Working directory {} doesn't exist in 'cmdrun --cwd missing echo'",
+            Path::new(&options.working_dir).join("missing").display()
+        )
+    );
+    assert_eq!(
+        run("--cwd @home/tools echo", &options),
+        "**cmdrun error**: Unknown directory '@home', expected @root, @src or @repo in 'cmdrun --cwd @home/tools echo'"
+    );
+
+    let options = common::options_in(".");
+    assert_eq!(
+        run("--cwd @root/tools echo", &options),
+        "**cmdrun error**: '@root' can only be used when building a book in 'cmdrun --cwd @root/tools echo'"
+    );
+}
+
+#[test]
+fn working_dir_policy() {
+    let working_dir = |working_dir| {
+        let config = Config {
+            working_dir,
+            ..Default::default()
+        };
+        PathBuf::from(
+            book_options(&config)
+                .for_chapter(&config, &chapter())
+                .working_dir,
+        )
+    };
+
+    assert_eq!(
+        working_dir(WorkingDir::Chapter),
+        PathBuf::from("tests/book/src/nested")
+    );
+    assert_eq!(
+        working_dir(WorkingDir::BookRoot),
+        PathBuf::from("tests/book")
+    );
+    assert_eq!(
+        working_dir(WorkingDir::Src),
+        PathBuf::from("tests/book/src")
+    );
+    assert_eq!(
+        working_dir(WorkingDir::RepoRoot),
+        dunce::canonicalize(".").unwrap()
+    );
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use std::path::Path;
+
+use common::config;
+
+use mdbook_cmdrun::cmdrun::RunOptions;
+use mdbook_cmdrun::config::Config;
+use mdbook_cmdrun::config::DefaultExitCode;
+
+fn run(command: &str, config: &Config) -> String {
+    let options = RunOptions {
+        working_dir: ".".to_string(),
+        ..RunOptions::for_book(config, Path::new("."), Path::new("src")).unwrap()
+    };
+    common::run(command, &options)
+}
+
+#[test]
+fn parse() {
+    let parse = |value: &str| {
+        config(&format!(
+            "[preprocessor.cmdrun]\ndefault-exit-code = {value}"
+        ))
+        .map(|config| config.default_exit_code)
+    };
+    assert_eq!(parse("0").unwrap(), DefaultExitCode::Code(0));
+    assert_eq!(parse("\"any\"").unwrap(), DefaultExitCode::Any);
+    assert!(parse("\"all\"").is_err());
+    assert_eq!(config("").unwrap().default_exit_code, DefaultExitCode::Any);
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn strict_by_default() {
+    let strict = config("[preprocessor.cmdrun]\ndefault-exit-code = 0").unwrap();
+    assert_eq!(
+        run("echo out; exit 1", &strict),
+        "**cmdrun error**: 'echo out; exit 1' returned exit code 1 instead of 0.\nout\n\n"
+    );
+    assert_eq!(run("--any-exit echo out; exit 1", &strict), "out");
+    assert_eq!(run("-1 echo out; exit 1", &strict), "out");
+
+    let any = config("[preprocessor.cmdrun]\ndefault-exit-code = \"any\"").unwrap();
+    assert_eq!(run("echo out; exit 1", &any), "out");
+}
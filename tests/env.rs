@@ -0,0 +1,49 @@
+mod common;
+
+use mdbook::book::SectionNumber;
+use mdbook_cmdrun::cmdrun::RunOptions;
+use mdbook_cmdrun::config::Config;
+use mdbook_cmdrun::CmdRun;
+
+#[test]
+#[cfg(target_family = "unix")]
+fn chapter_and_book() {
+    let mut chapter = common::chapter();
+    chapter.number = Some(SectionNumber(vec![2, 1]));
+    let options = RunOptions {
+        renderer: Some("html".to_string()),
+        ..common::book_options(&Config::default())
+    }
+    .for_chapter(&Config::default(), &chapter);
+
+    let book_root = dunce::canonicalize("tests/book").unwrap();
+    let output = CmdRun::run_on_content_with(
+        "<!-- cmdrun echo first -->\n<!-- cmdrun env | grep ^MDBOOK_CMDRUN_ | sort -->\n",
+        &options,
+    )
+    .unwrap();
+
+    assert_eq!(
+        output,
+        format!(
+            "first\n\
+             MDBOOK_CMDRUN_BOOK_ROOT={0}\n\
+             MDBOOK_CMDRUN_CHAPTER_NAME=Echo again\n\
+             MDBOOK_CMDRUN_CHAPTER_PATH=nested/echo_again.md\n\
+             MDBOOK_CMDRUN_DIRECTIVE_INDEX=2\n\
+             MDBOOK_CMDRUN_RENDERER=html\n\
+             MDBOOK_CMDRUN_SECTION_NUMBER=2.1\n\
+             MDBOOK_CMDRUN_SRC_DIR={0}/src\n",
+            book_root.display()
+        )
+    );
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn without_book() {
+    let output =
+        CmdRun::run_cmdrun("env | grep -c ^MDBOOK_CMDRUN_".to_string(), ".", true).unwrap();
+
+    assert_eq!(output, "0");
+}
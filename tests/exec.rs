@@ -0,0 +1,56 @@
+mod common;
+
+use mdbook_cmdrun::cmdrun::RunOptions;
+
+fn run(command: &str, exec: bool) -> String {
+    let options = RunOptions {
+        exec,
+        ..common::options_in(".")
+    };
+    common::run(command, &options)
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn no_shell() {
+    assert_eq!(
+        run("--exec printf '%s|' 'a b' \"c d\" e\\ f", false),
+        "a b|c d|e f|"
+    );
+    // Shell syntax is given to the program as is
+    assert_eq!(
+        run("--exec echo $HOME; ls > out.txt", false),
+        "$HOME; ls > out.txt"
+    );
+    assert_eq!(run("echo $HOME; ls", true), "$HOME; ls");
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn shell() {
+    assert_eq!(run("--shell echo 'a'; echo b", true), "a\nb");
+    assert_eq!(run("echo 'a'; echo b", false), "a\nb");
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn exit_code() {
+    assert_eq!(
+        run("--exec --strict false", false),
+        "**cmdrun error**: 'false' returned exit code 1 instead of 0.\n\n"
+    );
+}
+
+#[test]
+fn errors() {
+    assert!(run("--exec mdbook-cmdrun-no-such-program arg", false)
+        .starts_with("**cmdrun error**: Unable to run 'mdbook-cmdrun-no-such-program': "));
+    assert_eq!(
+        run("--exec", false),
+        "**cmdrun error**: No program to run in 'cmdrun --exec'"
+    );
+    assert_eq!(
+        run("--exec echo 'a", false),
+        "**cmdrun error**: Unterminated quote ' in 'cmdrun --exec echo 'a'"
+    );
+}
@@ -0,0 +1,31 @@
+use std::fs;
+
+use mdbook_cmdrun::CmdRun;
+use tempfile::TempDir;
+
+#[test]
+// Colons can't be part of file names on Windows, where they are used by drives
+#[cfg(target_family = "unix")]
+fn path_with_colons() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("C:")).unwrap();
+    fs::write(dir.path().join("C:/lines.txt"), "one\ntwo\nthree\n").unwrap();
+    let path = dir.path().join("C:/lines.txt").display().to_string();
+    let run = |spec: &str| {
+        CmdRun::run_cmdrun(
+            format!("--include {spec}"),
+            &dir.path().display().to_string(),
+            false,
+        )
+        .unwrap()
+    };
+
+    assert_eq!(run("C:/lines.txt"), "one\ntwo\nthree\n");
+    assert_eq!(run("C:/lines.txt:2"), "two\n");
+    assert_eq!(run("C:/lines.txt:2:"), "two\nthree\n");
+    assert_eq!(run("C:/lines.txt::2"), "one\ntwo\n");
+    assert_eq!(run(&format!("{path}:1:2")), "one\ntwo\n");
+    assert!(
+        run("C:/missing.txt:2").starts_with("**cmdrun error**: Unable to read file C:/missing.txt")
+    );
+}
@@ -0,0 +1,165 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use mdbook_cmdrun::artifact::ArtifactDir;
+use mdbook_cmdrun::cmdrun::RunOptions;
+use mdbook_cmdrun::CmdRun;
+use tempfile::TempDir;
+
+fn chapter_dir() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("input.txt"), "input\n").unwrap();
+    fs::create_dir(dir.path().join("data")).unwrap();
+    fs::write(dir.path().join("data/values.txt"), "1 2 3\n").unwrap();
+    dir
+}
+
+fn run(command: &str, dir: &TempDir, isolated: bool) -> String {
+    let options = RunOptions {
+        isolated,
+        ..common::options_in(&dir.path().to_string_lossy())
+    };
+    common::run(command, &options)
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn side_effects_are_discarded() {
+    let dir = chapter_dir();
+
+    let output = run(
+        "--isolated cat input.txt data/values.txt > output.txt; rm input.txt; cat output.txt",
+        &dir,
+        false,
+    );
+    assert_eq!(output, "input\n1 2 3");
+    assert!(dir.path().join("input.txt").exists());
+    assert!(!dir.path().join("output.txt").exists());
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn isolated_by_default() {
+    let dir = chapter_dir();
+
+    assert_eq!(run("echo side effect > output.txt", &dir, true), "");
+    assert!(!dir.path().join("output.txt").exists());
+
+    assert_eq!(run("echo side effect > output.txt", &dir, false), "");
+    assert!(dir.path().join("output.txt").exists());
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn keep() {
+    let dir = chapter_dir();
+
+    let output = run(
+        "--isolated --keep result.txt,data/summary.txt echo result > result.txt; echo 6 > data/summary.txt; touch other.txt",
+        &dir,
+        false,
+    );
+    assert_eq!(output, "");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("result.txt")).unwrap(),
+        "result\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("data/summary.txt")).unwrap(),
+        "6\n"
+    );
+    assert!(!dir.path().join("other.txt").exists());
+
+    let output = run("--isolated --keep missing.txt true", &dir, false);
+    assert!(output.starts_with("**cmdrun error**: Unable to read kept file missing.txt"));
+
+    assert_eq!(
+        run("--keep result.txt echo hi", &dir, false),
+        "**cmdrun error**: '--keep' can only be used along with '--isolated' in 'cmdrun --keep result.txt echo hi'"
+    );
+    assert_eq!(run("--keep result.txt echo hi", &dir, true), "hi");
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn artifact() {
+    let dir = chapter_dir();
+
+    let output = run(
+        "--isolated --artifact plot.svg --embed data-uri echo '<svg/>' > plot.svg",
+        &dir,
+        false,
+    );
+    assert_eq!(
+        output,
+        "![plot.svg](data:image/svg+xml;base64,PHN2Zy8+Cg==)"
+    );
+    assert!(!dir.path().join("plot.svg").exists());
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn artifacts_with_same_name() {
+    let dir = chapter_dir();
+    let src = TempDir::new().unwrap();
+    let options = RunOptions {
+        working_dir: dir.path().to_string_lossy().into_owned(),
+        artifact_dir: Some(ArtifactDir::new(
+            src.path(),
+            "cmdrun-artifacts",
+            Path::new("chapter.md"),
+        )),
+        directive_index: Some(2),
+        ..Default::default()
+    };
+    let run = |command: &str| {
+        CmdRun::run_directive(command.to_string(), true, &options)
+            .unwrap()
+            .text
+    };
+
+    assert_eq!(
+        run("--isolated --artifact a/plot.svg mkdir a; echo a > a/plot.svg"),
+        "![plot.svg](cmdrun-artifacts/chapter/a/plot.svg)"
+    );
+    assert_eq!(
+        run("--isolated --artifact b/plot.svg mkdir b; echo b > b/plot.svg"),
+        "![plot.svg](cmdrun-artifacts/chapter/b/plot.svg)"
+    );
+    assert_eq!(
+        run("--cwd data --artifact ../input.txt true"),
+        "[input.txt](cmdrun-artifacts/chapter/2-input.txt)"
+    );
+    let copied = src.path().join("cmdrun-artifacts/chapter");
+    assert_eq!(
+        fs::read_to_string(copied.join("a/plot.svg")).unwrap(),
+        "a\n"
+    );
+    assert_eq!(
+        fs::read_to_string(copied.join("b/plot.svg")).unwrap(),
+        "b\n"
+    );
+    assert_eq!(
+        fs::read_to_string(copied.join("2-input.txt")).unwrap(),
+        "input\n"
+    );
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn links_and_repositories() {
+    let dir = chapter_dir();
+    fs::create_dir(dir.path().join(".git")).unwrap();
+    fs::create_dir(dir.path().join("target")).unwrap();
+    std::os::unix::fs::symlink("..", dir.path().join("data/parent")).unwrap();
+    std::os::unix::fs::symlink("input.txt", dir.path().join("link.txt")).unwrap();
+
+    let output = run(
+        "--isolated ls -A; readlink data/parent; cat link.txt",
+        &dir,
+        false,
+    );
+    assert_eq!(output, "data\ninput.txt\nlink.txt\n..\ninput");
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::run;
+use mdbook::book::Book;
+use mdbook::book::BookItem;
+use mdbook::book::Chapter;
+use mdbook_cmdrun::cmdrun::RunOptions;
+use mdbook_cmdrun::config::Config;
+use mdbook_cmdrun::CmdRun;
+
+fn chapter(name: &str, content: &str) -> Chapter {
+    Chapter::new(name, content.to_string(), format!("{name}.md"), vec![])
+}
+
+fn options(chapter: &Chapter) -> RunOptions {
+    let mut book = Book::new();
+    book.push_item(BookItem::Chapter(chapter.clone()));
+    book.push_item(BookItem::Chapter(self::chapter("other", "")));
+
+    RunOptions {
+        book_json: Some(serde_json::to_string(&book).unwrap().into()),
+        ..common::book_options(&Config::default())
+    }
+    .for_chapter(&Config::default(), chapter)
+}
+
+#[test]
+fn chapter_json() {
+    let command = "--with-chapter-json python3 -c \"import json,sys; c = json.load(sys.stdin); print(c['name'], c['content'].splitlines()[0])\"";
+    let chapter = chapter("intro", &format!("# Intro\n<!-- cmdrun {command} -->\n"));
+
+    assert_eq!(run(command, &options(&chapter)), "intro # Intro");
+}
+
+#[test]
+fn only_when_used() {
+    let chapter = chapter("intro", "# Intro\n<!-- cmdrun echo --with-book-json -->\n");
+    let options = options(&chapter);
+
+    assert_eq!(options.chapter.unwrap().json, None);
+
+    let mut book = Book::new();
+    book.push_item(BookItem::Chapter(chapter));
+    assert!(RunOptions::book_json(&book).unwrap().is_some());
+    book.push_item(BookItem::Chapter(self::chapter(
+        "other",
+        "<!-- cmdrun echo -->",
+    )));
+    book.sections.remove(0);
+    assert_eq!(RunOptions::book_json(&book).unwrap(), None);
+}
+
+#[test]
+fn book_json() {
+    let chapter = chapter("intro", "");
+
+    assert_eq!(
+        run(
+            "--with-book-json python3 -c \"import json,sys; print(','.join(s['Chapter']['name'] for s in json.load(sys.stdin)['sections']))\"",
+            &options(&chapter)
+        ),
+        "intro,other"
+    );
+}
+
+#[test]
+fn large_input() {
+    let chapter = chapter(
+        "large",
+        &format!(
+            "<!-- cmdrun --with-chapter-json -->\n{}",
+            "x".repeat(1 << 20)
+        ),
+    );
+
+    assert_eq!(
+        run(
+            "--with-chapter-json python3 -c \"import sys; print(len(sys.stdin.read()) > 1 << 20)\"",
+            &options(&chapter)
+        ),
+        "True"
+    );
+    // The input doesn't have to be read
+    assert_eq!(
+        run("--with-chapter-json echo ignored", &options(&chapter)),
+        "ignored"
+    );
+}
+
+#[test]
+fn errors() {
+    let chapter = chapter("intro", "");
+
+    assert_eq!(
+        run(
+            "--with-book-json --with-chapter-json echo",
+            &options(&chapter)
+        ),
+        "**cmdrun error**: '--with-book-json' and '--with-chapter-json' can't be used together in 'cmdrun --with-book-json --with-chapter-json echo'"
+    );
+    assert_eq!(
+        CmdRun::run_cmdrun("--with-book-json echo".to_string(), ".", true).unwrap(),
+        "**cmdrun error**: '--with-book-json' can only be used when building a book in 'cmdrun --with-book-json echo'"
+    );
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use std::env;
+
+use mdbook_cmdrun::cmdrun::RunOptions;
+use mdbook_cmdrun::config::NormalizeConfig;
+use mdbook_cmdrun::normalize::LineEndings;
+use mdbook_cmdrun::normalize::Normalize;
+
+fn run(command: &str, inline: bool, config: NormalizeConfig) -> String {
+    let options = RunOptions {
+        normalize: Normalize::new(&config, &env::current_dir().unwrap()),
+        ..common::options_in(".")
+    };
+    common::output(command, inline, &options)
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn trailing_whitespace() {
+    let config = NormalizeConfig {
+        trailing_whitespace: true,
+        ..Default::default()
+    };
+    assert_eq!(run("printf 'a  \\nb\\t\\n'", false, config), "a\nb\n");
+    assert_eq!(
+        run("printf 'a  \\nb\\t\\n'", false, NormalizeConfig::default()),
+        "a  \nb\t\n"
+    );
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn line_endings() {
+    let lf = NormalizeConfig {
+        line_endings: LineEndings::Lf,
+        ..Default::default()
+    };
+    assert_eq!(run("printf 'a\\r\\nb\\n'", false, lf), "a\nb\n");
+
+    let crlf = NormalizeConfig {
+        line_endings: LineEndings::Crlf,
+        ..Default::default()
+    };
+    assert_eq!(run("printf 'a\\r\\nb\\n'", false, crlf), "a\r\nb\r\n");
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn paths() {
+    let config = || NormalizeConfig {
+        paths: true,
+        ..Default::default()
+    };
+    assert_eq!(run("pwd", true, config()), ".");
+    assert_eq!(
+        run("echo \"$(pwd)/src/lib.rs:1\"", true, config()),
+        "src/lib.rs:1"
+    );
+    // Only whole path components are replaced
+    assert_eq!(run("echo \"$(pwd)-backup\"", true, config()), ".-backup");
+    assert_eq!(
+        run("echo \"$(pwd)backup\"", true, config()),
+        format!("{}backup", env::current_dir().unwrap().display())
+    );
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn sort() {
+    let config = NormalizeConfig {
+        sort: true,
+        ..Default::default()
+    };
+    assert_eq!(run("printf 'b\\nc\\na\\n'", false, config), "a\nb\nc\n");
+    assert_eq!(
+        run(
+            "--sort printf 'b\\nc\\na\\n'",
+            false,
+            NormalizeConfig::default()
+        ),
+        "a\nb\nc\n"
+    );
+    assert_eq!(
+        run("printf 'b\\nc\\na\\n'", false, NormalizeConfig::default()),
+        "b\nc\na\n"
+    );
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn error_output() {
+    let config = NormalizeConfig {
+        trailing_whitespace: true,
+        line_endings: LineEndings::Crlf,
+        ..Default::default()
+    };
+    assert_eq!(
+        run("--strict echo 'out  '; exit 2", false, config),
+        "**cmdrun error**: 'echo 'out  '; exit 2' returned exit code 2 instead of 0.\r\nout\r\n\r\n"
+    );
+}
+
+#[test]
+fn final_line_ending() {
+    let normalize = Normalize::default();
+
+    if cfg!(target_family = "windows") {
+        assert_eq!(normalize.whitespace("a\nb", false), "a\r\nb\r\n");
+        assert_eq!(normalize.whitespace("a\nb", true), "a\r\nb");
+    } else {
+        assert_eq!(normalize.whitespace("a\nb", false), "a\nb");
+        assert_eq!(normalize.whitespace("a\nb", true), "a\nb");
+    }
+    assert_eq!(normalize.whitespace("", false), "");
+}
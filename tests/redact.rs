@@ -0,0 +1,166 @@
+mod common;
+
+use mdbook_cmdrun::cmdrun::RunOptions;
+use mdbook_cmdrun::config::RedactConfig;
+use mdbook_cmdrun::redact::Redaction;
+
+// Environment of the preprocessor, rather than the real one which is shared by every test
+fn run_with_env(command: &str, config: RedactConfig, env: &[(&str, &str)]) -> String {
+    let redaction = Redaction::with_env(&config, |name| {
+        env.iter()
+            .chain(&[("HOSTNAME", "cmdrun-test-host")])
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value.to_string())
+    })
+    .unwrap();
+    let options = RunOptions {
+        redaction,
+        ..common::options_in(".")
+    };
+    common::run(command, &options)
+}
+
+fn run(command: &str, config: RedactConfig) -> String {
+    run_with_env(command, config, &[])
+}
+
+#[test]
+fn env_values() {
+    let config = RedactConfig {
+        env: vec!["CMDRUN_TEST_TOKEN".to_string()],
+        ..Default::default()
+    };
+
+    assert_eq!(
+        run_with_env(
+            "echo token: s3cr3t-t0k3n",
+            config,
+            &[("CMDRUN_TEST_TOKEN", "s3cr3t-t0k3n")]
+        ),
+        "token: [REDACTED]"
+    );
+}
+
+#[test]
+fn placeholder() {
+    let config = RedactConfig {
+        env: vec!["CMDRUN_TEST_PASSWORD".to_string()],
+        placeholder: "***".to_string(),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        run_with_env(
+            "echo password=hunter2",
+            config,
+            &[("CMDRUN_TEST_PASSWORD", "hunter2")]
+        ),
+        "password=***"
+    );
+}
+
+#[test]
+fn patterns() {
+    let config = RedactConfig {
+        patterns: vec![r"ghp_[A-Za-z0-9]+".to_string()],
+        ..Default::default()
+    };
+
+    assert_eq!(
+        run("echo Authorization: ghp_abc123", config),
+        "Authorization: [REDACTED]"
+    );
+}
+
+#[test]
+fn invalid_pattern() {
+    let config = RedactConfig {
+        patterns: vec!["(".to_string()],
+        ..Default::default()
+    };
+
+    let err = Redaction::new(&config).unwrap_err();
+    assert!(format!("{err:#}").starts_with("Invalid redaction pattern '('"));
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn home() {
+    let env = [("HOME", "/home/al")];
+
+    assert_eq!(
+        run_with_env(
+            "echo /home/al/notes /home/alice /home/al.bak '/home/al' /home/al",
+            RedactConfig::default(),
+            &env
+        ),
+        "~/notes /home/alice /home/al.bak ~ ~"
+    );
+
+    let config = RedactConfig {
+        home: false,
+        ..Default::default()
+    };
+    assert_eq!(
+        run_with_env("echo /home/al/notes", config, &env),
+        "/home/al/notes"
+    );
+}
+
+#[test]
+fn user() {
+    let env = [("USER", "runner")];
+
+    assert_eq!(
+        run_with_env("echo the runner", RedactConfig::default(), &env),
+        "the runner"
+    );
+
+    let config = RedactConfig {
+        user: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        run_with_env("echo the runner runners", config, &env),
+        "the [USER] runners"
+    );
+}
+
+#[test]
+fn hostname() {
+    assert_eq!(
+        run("echo on cmdrun-test-host", RedactConfig::default()),
+        "on [HOSTNAME]"
+    );
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn error_output() {
+    let config = RedactConfig {
+        env: vec!["CMDRUN_TEST_API_KEY".to_string()],
+        ..Default::default()
+    };
+
+    let output = run_with_env(
+        "--strict k=k3y-v4; echo key ${k}lu3; echo failed with ${k}lu3 >&2; exit 1",
+        config,
+        &[("CMDRUN_TEST_API_KEY", "k3y-v4lu3")],
+    );
+    assert!(output.contains("key [REDACTED]"));
+    assert!(output.contains("failed with [REDACTED]"));
+    assert!(!output.contains("k3y-v4lu3"));
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn cmdrun_errors() {
+    assert_eq!(
+        run_with_env(
+            "--include /home/al/missing.txt",
+            RedactConfig::default(),
+            &[("HOME", "/home/al")]
+        ),
+        "**cmdrun error**: Unable to read file ~/missing.txt in 'cmdrun --include /home/al/missing.txt'"
+    );
+}
@@ -73,22 +73,34 @@ fn check_all_regressions_dirs() {
         entries,
         vec![
             "bash_call",
+            "diff",
             "err_messages",
+            "escape",
+            "include",
             "inline_call",
+            "pipeline",
+            "platform",
             "py_factorial",
             "py_fibonacci",
             "py_readme",
             "rust_call",
             "shell",
+            "table",
         ]
     );
 }
 
 add_dir!(bash_call);
 add_dir!(inline_call);
+add_dir!(pipeline);
 add_dir!(py_readme);
 add_dir!(py_factorial);
 add_dir!(py_fibonacci);
 add_dir!(rust_call);
 add_dir!(shell);
+add_dir!(table);
+add_dir!(diff);
 add_dir!(err_messages);
+add_dir!(escape);
+add_dir!(include);
+add_dir!(platform);
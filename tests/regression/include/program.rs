@@ -0,0 +1,11 @@
+// ANCHOR: all
+use std::env;
+
+fn main() {
+    // ANCHOR: body
+    for arg in env::args() {
+        println!("{arg}");
+    }
+    // ANCHOR_END: body
+}
+// ANCHOR_END: all
@@ -0,0 +1,10 @@
+import json
+
+sales = [
+    {"product": "Juicy Apples", "price": 1.99, "stock": 7},
+    {"product": "Bananas", "price": 1.89, "stock": 5234, "note": "ripe | yellow"},
+    {"product": "Cherries", "price": None, "stock": 0},
+]
+
+if __name__ == "__main__":
+    print(json.dumps(sales))
@@ -0,0 +1,101 @@
+use mdbook_cmdrun::cmdrun::RunOptions;
+use mdbook_cmdrun::config::Escape;
+use mdbook_cmdrun::CmdRun;
+
+macro_rules! add_test {
+    ($name:ident, $cmd:literal, $renderer:literal, $output:expr $(,)?) => {
+        #[test]
+        fn $name() {
+            let options = RunOptions {
+                working_dir: ".".to_string(),
+                renderer: Some($renderer.to_string()),
+                ..Default::default()
+            };
+            let actual_output = CmdRun::run_directive($cmd.to_string(), true, &options)
+                .unwrap()
+                .text;
+
+            assert_eq!(actual_output, $output.to_string());
+        }
+    };
+}
+
+add_test!(no_condition, "echo all", "epub", "all");
+add_test!(
+    matching_renderer,
+    "--renderer html echo html",
+    "html",
+    "html"
+);
+add_test!(
+    matching_one_of_renderers,
+    "--renderer html,epub echo some",
+    "epub",
+    "some"
+);
+add_test!(
+    skipped_renderer,
+    "--renderer html echo html",
+    "markdown",
+    ""
+);
+add_test!(
+    else_matching_renderer,
+    "--renderer html --else 'echo plain text' echo '<b>html</b>'",
+    "html",
+    "<b>html</b>"
+);
+add_test!(
+    else_other_renderer,
+    "--renderer html --else 'echo plain text' echo '<b>html</b>'",
+    "markdown",
+    "plain text"
+);
+add_test!(
+    renderer_with_exit_code,
+    "--renderer markdown -1 --else 'exit 1' echo markdown",
+    "html",
+    ""
+);
+
+#[test]
+fn side_by_side_diff_only_for_html() {
+    let directive = "--diff tests/regression/diff/a.rs tests/regression/diff/b.rs --side-by-side";
+    let run = |renderer: &str| {
+        let options = RunOptions {
+            working_dir: ".".to_string(),
+            renderer: Some(renderer.to_string()),
+            ..Default::default()
+        };
+        CmdRun::run_directive(directive.to_string(), false, &options)
+            .unwrap()
+            .text
+    };
+
+    assert!(run("html").starts_with("<table"));
+    assert!(run("markdown").starts_with("--- tests/regression/diff/a.rs"));
+}
+
+#[test]
+fn side_by_side_diff_not_escaped() {
+    let options = RunOptions {
+        working_dir: ".".to_string(),
+        renderer: Some("html".to_string()),
+        escape: Escape::Html,
+        ..Default::default()
+    };
+    let run = |directive: &str| {
+        CmdRun::run_directive(directive.to_string(), false, &options)
+            .unwrap()
+            .text
+    };
+
+    let diff = "--diff tests/regression/diff/a.rs tests/regression/diff/b.rs --side-by-side";
+    assert!(run(diff).starts_with("<table"));
+    assert!(run(&format!("--escape pre {diff}")).starts_with("<table"));
+    // Unified diffs are text, escaped like the output of commands
+    assert!(
+        run("--escape html --diff tests/regression/diff/a.rs tests/regression/diff/b.rs")
+            .contains("&quot;")
+    );
+}
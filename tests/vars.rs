@@ -0,0 +1,91 @@
+mod common;
+
+use mdbook_cmdrun::cmdrun::RunOptions;
+use mdbook_cmdrun::CmdRun;
+
+fn options() -> RunOptions {
+    let config = common::config(
+        r#"
+        [preprocessor.cmdrun.vars]
+        example_dir = "examples"
+        spaced = "my file.txt"
+        quoted = "it's"
+        special = "$(echo run) `echo run` \\ \"x\" $HOME"
+        version = 2
+        "#,
+    )
+    .unwrap();
+    common::chapter_options(&config)
+}
+
+fn run(command: &str) -> String {
+    common::run(command, &options())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn configured() {
+    assert_eq!(
+        run("echo {{example_dir}}/x v{{ version }}"),
+        "examples/x v2"
+    );
+    assert_eq!(run("printf '%s\\n' {{spaced}}"), "my file.txt");
+    assert_eq!(run("echo {{quoted}}"), "it's");
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn inside_quotes() {
+    let special = r#"$(echo run) `echo run` \ "x" $HOME"#;
+
+    assert_eq!(run("echo \"{{spaced}}: {{quoted}}\""), "my file.txt: it's");
+    assert_eq!(run("echo \"[{{special}}]\""), format!("[{special}]"));
+    assert_eq!(
+        run("echo '{{quoted}} [{{special}}]'"),
+        format!("it's [{special}]")
+    );
+    assert_eq!(
+        run("--expect-contains \"{{special}}\" --expect-contains '{{quoted}}' echo {{special}} {{quoted}}"),
+        format!("{special} it's")
+    );
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn built_in() {
+    let book_root = dunce::canonicalize("tests/book").unwrap();
+
+    assert_eq!(run("echo {{book_root}}"), book_root.display().to_string());
+    assert_eq!(run("echo {{root}}"), book_root.display().to_string());
+    assert_eq!(
+        run("echo {{src_dir}}"),
+        book_root.join("src").display().to_string()
+    );
+    assert_eq!(
+        run("echo {{chapter_dir}}"),
+        book_root.join("src/nested").display().to_string()
+    );
+    assert_eq!(run("--cwd {{src_dir}} ls SUMMARY.md"), "SUMMARY.md");
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn escaped() {
+    assert_eq!(
+        run("echo '\\{{name}} \\{{ version }}'"),
+        "{{name}} {{ version }}"
+    );
+    assert_eq!(run("echo \\{{version}}={{version}}"), "{{version}}=2");
+}
+
+#[test]
+fn unknown() {
+    assert_eq!(
+        run("echo {{nope}}"),
+        "**cmdrun error**: Unknown variable 'nope' in 'cmdrun echo {{nope}}'"
+    );
+    assert_eq!(
+        CmdRun::run_cmdrun("echo {{book_root}}".to_string(), ".", true).unwrap(),
+        "**cmdrun error**: Unknown variable 'book_root' in 'cmdrun echo {{book_root}}'"
+    );
+}